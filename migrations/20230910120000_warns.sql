@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS "warns" (
+    "warn_id" SERIAL PRIMARY KEY,
+    "chat_id" BIGINT NOT NULL,
+    "user_id" BIGINT NOT NULL,
+    "reason" TEXT,
+    "issuer_id" BIGINT NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT "fk_warns" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
+
+CREATE INDEX "idx_warns_chat_user" on "warns" ("chat_id", "user_id");
+
+CREATE TABLE IF NOT EXISTS "warn_settings" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "warn_limit" INT NOT NULL DEFAULT 3,
+    "warn_mode" TEXT NOT NULL DEFAULT 'ban',
+    "warn_mode_duration" BIGINT,
+    CONSTRAINT "fk_warn_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1 @@
+ALTER TABLE "warn_settings" ADD COLUMN IF NOT EXISTS "warn_time" BIGINT;
@@ -0,0 +1,6 @@
+-- for groups this toggles reporting, for private chats whether reports are sent by PM
+CREATE TABLE IF NOT EXISTS "report_settings" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "enabled" BOOLEAN NOT NULL,
+    CONSTRAINT "fk_report_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
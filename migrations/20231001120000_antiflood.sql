@@ -0,0 +1,11 @@
+-- a limit or timer of 0 means that check is disabled
+CREATE TABLE IF NOT EXISTS "flood_settings" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "flood_limit" INT NOT NULL DEFAULT 0,
+    "flood_timer_count" INT NOT NULL DEFAULT 0,
+    "flood_timer_secs" INT NOT NULL DEFAULT 0,
+    "flood_mode" TEXT NOT NULL DEFAULT 'mute',
+    "flood_mode_duration" BIGINT,
+    "clear_flood" BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT "fk_flood_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,11 @@
+-- welcomes are on by default, a NULL text means the default welcome is used
+CREATE TABLE IF NOT EXISTS "greetings" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "welcome_enabled" BOOLEAN NOT NULL DEFAULT TRUE,
+    "welcome_text" TEXT,
+    "welcome_media_kind" TEXT,
+    "welcome_file_id" TEXT,
+    "clean_welcome" BOOLEAN NOT NULL DEFAULT FALSE,
+    "last_welcome_id" INT,
+    CONSTRAINT "fk_greetings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,7 @@
+-- goodbyes are off by default, a NULL text means the default goodbye is used
+ALTER TABLE "greetings"
+    ADD COLUMN "goodbye_enabled" BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN "goodbye_text" TEXT,
+    ADD COLUMN "goodbye_media_kind" TEXT,
+    ADD COLUMN "goodbye_file_id" TEXT,
+    ADD COLUMN "goodbye_kicked" BOOLEAN NOT NULL DEFAULT TRUE;
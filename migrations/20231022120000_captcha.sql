@@ -0,0 +1,8 @@
+-- captcha_time is in seconds, newcomers that don't solve the captcha in time are kicked
+CREATE TABLE IF NOT EXISTS "captcha_settings" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "captcha_enabled" BOOLEAN NOT NULL DEFAULT FALSE,
+    "captcha_mode" TEXT NOT NULL DEFAULT 'button',
+    "captcha_time" BIGINT NOT NULL DEFAULT 300,
+    CONSTRAINT "fk_captcha_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
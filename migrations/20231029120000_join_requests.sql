@@ -0,0 +1,8 @@
+-- join_log_chat receives the answers to join questions, the chat itself is used if it's NULL
+CREATE TABLE IF NOT EXISTS "join_settings" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "join_mode" TEXT NOT NULL DEFAULT 'manual',
+    "join_questions" TEXT[] NOT NULL DEFAULT '{}',
+    "join_log_chat" BIGINT,
+    CONSTRAINT "fk_join_settings" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,6 @@
+-- the kinds of service messages deleted in a chat, eg. join or pin
+CREATE TABLE IF NOT EXISTS "clean_service" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "service_kinds" TEXT[] NOT NULL DEFAULT '{}',
+    CONSTRAINT "fk_clean_service" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "rules" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "rules_text" TEXT,
+    "private_rules" BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT "fk_rules" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,6 @@
+-- the kinds of messages non-admins aren't allowed to send in a chat
+CREATE TABLE IF NOT EXISTS "locks" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "lock_kinds" TEXT[] NOT NULL DEFAULT '{}',
+    CONSTRAINT "fk_locks" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,9 @@
+-- approved users are exempt from locks and antiflood
+CREATE TABLE IF NOT EXISTS "approvals" (
+    "chat_id" BIGINT,
+    "user_id" BIGINT,
+    "approved_by" BIGINT NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY ("chat_id", "user_id"),
+    CONSTRAINT "fk_approvals" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
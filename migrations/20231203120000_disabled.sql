@@ -0,0 +1,7 @@
+-- the commands non-admins aren't allowed to use in a chat
+CREATE TABLE IF NOT EXISTS "disabled_commands" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "commands" TEXT[] NOT NULL DEFAULT '{}',
+    "delete_disabled" BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT "fk_disabled_commands" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,7 @@
+-- the channel a chat logs its moderation actions to, and which kinds of actions it logs
+CREATE TABLE IF NOT EXISTS "log_channels" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "log_chat_id" BIGINT,
+    "log_categories" TEXT[] NOT NULL DEFAULT '{settings,admin,warns,notes,automated}',
+    CONSTRAINT "fk_log_channels" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
@@ -0,0 +1,32 @@
+-- federations share a ban list between the chats that join them
+CREATE TABLE IF NOT EXISTS "feds" (
+    "fed_id" TEXT PRIMARY KEY DEFAULT gen_random_uuid()::TEXT,
+    "fed_name" TEXT NOT NULL,
+    "owner_id" BIGINT NOT NULL UNIQUE,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- a chat is part of at most one federation
+CREATE TABLE IF NOT EXISTS "fed_chats" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "fed_id" TEXT NOT NULL,
+    CONSTRAINT "fk_fed_chats_chat" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id"),
+    CONSTRAINT "fk_fed_chats_fed" FOREIGN KEY ("fed_id") REFERENCES "feds" ("fed_id") ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS "fed_admins" (
+    "fed_id" TEXT,
+    "user_id" BIGINT,
+    PRIMARY KEY ("fed_id", "user_id"),
+    CONSTRAINT "fk_fed_admins" FOREIGN KEY ("fed_id") REFERENCES "feds" ("fed_id") ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS "fed_bans" (
+    "fed_id" TEXT,
+    "user_id" BIGINT,
+    "reason" TEXT,
+    "banned_by" BIGINT NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY ("fed_id", "user_id"),
+    CONSTRAINT "fk_fed_bans" FOREIGN KEY ("fed_id") REFERENCES "feds" ("fed_id") ON DELETE CASCADE
+);
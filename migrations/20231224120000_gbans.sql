@@ -0,0 +1,14 @@
+-- users banned by sudo users from every chat the bot manages
+CREATE TABLE IF NOT EXISTS "gbans" (
+    "user_id" BIGINT PRIMARY KEY,
+    "reason" TEXT,
+    "banned_by" BIGINT NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- chats that opted out of global bans
+CREATE TABLE IF NOT EXISTS "antispam" (
+    "chat_id" BIGINT PRIMARY KEY,
+    "antispam_enabled" BOOLEAN NOT NULL DEFAULT TRUE,
+    CONSTRAINT "fk_antispam" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
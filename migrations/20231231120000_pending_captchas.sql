@@ -0,0 +1,10 @@
+-- captchas newcomers still have to solve, kept so they expire even across restarts
+CREATE TABLE IF NOT EXISTS "pending_captchas" (
+    "chat_id" BIGINT,
+    "user_id" BIGINT,
+    "message_id" INTEGER NOT NULL,
+    "answer" TEXT NOT NULL,
+    "expires_at" TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY ("chat_id", "user_id"),
+    CONSTRAINT "fk_pending_captchas" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
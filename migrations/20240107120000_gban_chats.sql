@@ -0,0 +1,8 @@
+-- the chats a global ban was applied in, so lifting it leaves bans of the chats themselves alone
+CREATE TABLE IF NOT EXISTS "gban_chats" (
+    "user_id" BIGINT,
+    "chat_id" BIGINT,
+    PRIMARY KEY ("user_id", "chat_id"),
+    CONSTRAINT "fk_gban_chats_gban" FOREIGN KEY ("user_id") REFERENCES "gbans" ("user_id") ON DELETE CASCADE,
+    CONSTRAINT "fk_gban_chats_chat" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
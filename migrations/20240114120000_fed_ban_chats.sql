@@ -0,0 +1,9 @@
+-- the chats a federation ban was applied in, so lifting it leaves bans of the chats themselves alone
+CREATE TABLE IF NOT EXISTS "fed_ban_chats" (
+    "fed_id" TEXT,
+    "user_id" BIGINT,
+    "chat_id" BIGINT,
+    PRIMARY KEY ("fed_id", "user_id", "chat_id"),
+    CONSTRAINT "fk_fed_ban_chats_ban" FOREIGN KEY ("fed_id", "user_id") REFERENCES "fed_bans" ("fed_id", "user_id") ON DELETE CASCADE,
+    CONSTRAINT "fk_fed_ban_chats_chat" FOREIGN KEY ("chat_id") REFERENCES "chats" ("chat_id")
+);
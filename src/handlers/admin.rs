@@ -1,3 +1,731 @@
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{ChatMember, ChatMemberKind, ChatPermissions},
+    utils::html,
+    RequestError,
+};
 
+use crate::{
+    handlers::log_channel::LogEntry,
+    types::{LogCategory, Punishment},
+    utils::{self, admin_cache, perms, time},
+};
 
+/// How long the ban behind a kick lasts, Telegram treats anything under 30s as permanent.
+const KICK_BAN_SECS: i64 = 60;
+/// How often lifting the ban behind a kick is tried.
+const KICK_UNBAN_ATTEMPTS: usize = 3;
 
+/// Resolves the user an admin command is aimed at, along with the reason given for it.
+///
+/// Replies to the message and returns `None` if no user could be found.
+pub async fn extract_target(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<(ChatMember, Option<String>)>> {
+    let (user_id, text) = utils::extract_user_and_text(bot, message, pool).await;
+
+    let user_id = match user_id {
+        Some(id) => UserId(id),
+        None => {
+            bot.send_message(
+                message.chat.id,
+                "You need to reply to a user, or give me their username or ID!",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(None);
+        }
+    };
+
+    // a bare command used as a reply carries no reason of its own
+    let has_args = message
+        .text()
+        .map(|t| t.split_whitespace().count() > 1)
+        .unwrap_or_default();
+    let reason = text.filter(|t| has_args && !t.trim().is_empty());
+
+    let member = bot.get_chat_member(message.chat.id, user_id).await?;
+
+    Ok(Some((member, reason)))
+}
+
+/// Checks that the target of a restriction is neither the bot nor a chat admin.
+pub async fn can_restrict_target(
+    bot: &crate::types::TBot,
+    message: &Message,
+    member: &ChatMember,
+    action: &str,
+) -> anyhow::Result<bool> {
+    let refusal = if member.user.id.0 as i64 == *crate::BOT_ID {
+        format!("I'm not going to {action} myself!")
+    } else if perms::is_user_admin(bot, message, member.user.id)
+        .await
+        .is_ok()
+    {
+        format!("I can't {action} an admin!")
+    } else {
+        return Ok(true);
+    };
+
+    bot.send_message(message.chat.id, refusal)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(false)
+}
+
+/// Removes a user from the chat without banning them, so they are able to rejoin.
+pub async fn kick_member(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+) -> Result<(), RequestError> {
+    // the ban lifts itself shortly, in case the unban below keeps failing
+    bot.ban_chat_member(chat_id, user_id)
+        .until_date(Utc::now() + Duration::seconds(KICK_BAN_SECS))
+        .await?;
+
+    for _ in 0..KICK_UNBAN_ATTEMPTS {
+        match bot.unban_chat_member(chat_id, user_id).await {
+            Ok(_) => return Ok(()),
+            Err(RequestError::RetryAfter(wait)) => tokio::time::sleep(wait).await,
+            Err(e) => log::debug!("Unable to unban kicked {user_id} in {chat_id}: {e}"),
+        }
+    }
+
+    log::warn!("Gave up unbanning kicked {user_id} in {chat_id}, the ban lifts itself soon");
+    Ok(())
+}
+
+/// Applies a punishment to a user, timed punishments last for the given duration.
+pub async fn punish(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+    punishment: Punishment,
+    duration: Option<Duration>,
+) -> Result<(), RequestError> {
+    let until = duration
+        .filter(|_| punishment.is_timed())
+        .map(|d| Utc::now() + d);
+
+    match (punishment, until) {
+        (Punishment::Kick, _) => kick_member(bot, chat_id, user_id).await?,
+        (Punishment::Ban, _) | (Punishment::TBan, None) => {
+            bot.ban_chat_member(chat_id, user_id).await?;
+        }
+        (Punishment::TBan, Some(until)) => {
+            bot.ban_chat_member(chat_id, user_id)
+                .until_date(until)
+                .await?;
+        }
+        (Punishment::Mute, _) | (Punishment::TMute, None) => {
+            bot.restrict_chat_member(chat_id, user_id, ChatPermissions::empty())
+                .await?;
+        }
+        (Punishment::TMute, Some(until)) => {
+            bot.restrict_chat_member(chat_id, user_id, ChatPermissions::empty())
+                .until_date(until)
+                .await?;
+        }
+    };
+
+    Ok(())
+}
+
+/// Parses a punishment along with its duration, which timed punishments require.
+pub fn parse_punishment(
+    mode: &str,
+    duration: Option<&str>,
+) -> anyhow::Result<(Punishment, Option<Duration>)> {
+    let punishment: Punishment = mode.parse()?;
+
+    let duration = if punishment.is_timed() {
+        Some(time::parse_duration(duration.unwrap_or_default())?)
+    } else {
+        None
+    };
+
+    Ok((punishment, duration))
+}
+
+/// Describes a punishment that has been applied, eg. `banned for 1d`.
+pub fn describe_punishment(punishment: Punishment, duration: Option<Duration>) -> String {
+    let action = match punishment {
+        Punishment::Ban | Punishment::TBan => "banned",
+        Punishment::Kick => "kicked",
+        Punishment::Mute | Punishment::TMute => "muted",
+    };
+
+    match duration.filter(|_| punishment.is_timed()) {
+        Some(duration) => format!("{action} for {}", time::format_duration(duration)),
+        None => action.to_owned(),
+    }
+}
+
+pub fn format_reason(reason: Option<&str>) -> String {
+    reason
+        .map(|r| format!("\nReason: {}", html::escape(r)))
+        .unwrap_or_default()
+}
+
+pub async fn mute(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, reason)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !can_restrict_target(bot, message, &member, "mute").await? {
+        return Ok(());
+    }
+
+    bot.restrict_chat_member(message.chat.id, member.user.id, ChatPermissions::empty())
+        .await?;
+
+    log::info!("Muted user {} in chat {}", member.user.id, message.chat.id);
+
+    let mut entry = LogEntry::new(LogCategory::Admin, "MUTE").user(&member.user);
+    if let Some(reason) = &reason {
+        entry = entry.detail("Reason", reason);
+    }
+    entry.send(bot, message, pool).await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Muted {}.{}",
+            html::user_mention(member.user.id.0 as i64, &member.user.full_name()),
+            format_reason(reason.as_deref())
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn temp_mute(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, text)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    // the first word of the text is the duration, the rest is the reason
+    let text = text.unwrap_or_default();
+    let (duration_text, reason) = match text.trim().split_once(char::is_whitespace) {
+        Some((duration_text, reason)) => (duration_text, Some(reason.trim())),
+        None => (text.trim(), None),
+    };
+
+    let duration = match time::parse_duration(duration_text) {
+        Ok(duration) => duration,
+        Err(e) => {
+            bot.send_message(message.chat.id, e.to_string())
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if !can_restrict_target(bot, message, &member, "mute").await? {
+        return Ok(());
+    }
+
+    bot.restrict_chat_member(message.chat.id, member.user.id, ChatPermissions::empty())
+        .until_date(Utc::now() + duration)
+        .await?;
+
+    log::info!(
+        "Muted user {} in chat {} for {}",
+        member.user.id,
+        message.chat.id,
+        duration
+    );
+
+    let mut entry = LogEntry::new(LogCategory::Admin, "TMUTE")
+        .user(&member.user)
+        .detail("Duration", time::format_duration(duration));
+    if let Some(reason) = reason {
+        entry = entry.detail("Reason", reason);
+    }
+    entry.send(bot, message, pool).await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Muted {} for {}.{}",
+            html::user_mention(member.user.id.0 as i64, &member.user.full_name()),
+            html::code_inline(&time::format_duration(duration)),
+            format_reason(reason)
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unmute(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, _)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !member.is_restricted() {
+        bot.send_message(message.chat.id, "This user isn't muted!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // give the user back the default permissions of the chat
+    let permissions = bot
+        .get_chat(message.chat.id)
+        .await?
+        .permissions()
+        .ok_or(anyhow!("Unable to access chat permissions"))?;
+
+    bot.restrict_chat_member(message.chat.id, member.user.id, permissions)
+        .await?;
+
+    log::info!(
+        "Unmuted user {} in chat {}",
+        member.user.id,
+        message.chat.id
+    );
+
+    LogEntry::new(LogCategory::Admin, "UNMUTE")
+        .user(&member.user)
+        .send(bot, message, pool)
+        .await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Unmuted {}.",
+            html::user_mention(member.user.id.0 as i64, &member.user.full_name())
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn kick(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, reason)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !member.is_present() {
+        bot.send_message(message.chat.id, "This user isn't in the chat!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    if !can_restrict_target(bot, message, &member, "kick").await? {
+        return Ok(());
+    }
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+
+    if let Err(e) = kick_member(bot, message.chat.id, member.user.id).await {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "I couldn't kick {mention}: {}",
+                html::escape(&e.to_string())
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    log::info!(
+        "Kicked user {} from chat {}",
+        member.user.id,
+        message.chat.id
+    );
+
+    let mut entry = LogEntry::new(LogCategory::Admin, "KICK").user(&member.user);
+    if let Some(reason) = &reason {
+        entry = entry.detail("Reason", reason);
+    }
+    entry.send(bot, message, pool).await;
+
+    bot.send_message(
+        message.chat.id,
+        format!("Kicked {mention}.{}", format_reason(reason.as_deref())),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn kick_me(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let user = message.from().ok_or(anyhow!("User not found"))?;
+
+    if perms::is_user_admin(bot, message, user.id).await.is_ok() {
+        bot.send_message(message.chat.id, "You're an admin, I can't kick you!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    if let Err(e) = kick_member(bot, message.chat.id, user.id).await {
+        bot.send_message(
+            message.chat.id,
+            format!("I couldn't kick you: {}", html::escape(&e.to_string())),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    bot.send_message(message.chat.id, "Sure, see you around!")
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Telegram limits custom admin titles to this many characters.
+const MAX_TITLE_LEN: usize = 16;
+
+async fn promote_member(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+    full: bool,
+) -> anyhow::Result<()> {
+    let caller = message.from().ok_or(anyhow!("User not found"))?;
+    let Some((member, title)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if member.user.id == caller.id {
+        bot.send_message(message.chat.id, "You can't promote yourself!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+
+    if member.is_owner() || (member.is_administrator() && !member.can_be_edited()) {
+        bot.send_message(
+            message.chat.id,
+            format!("{mention} is already an admin, and I can't edit their rights!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    if !member.is_present() {
+        bot.send_message(message.chat.id, "This user isn't in the chat!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    if title
+        .as_ref()
+        .is_some_and(|t| t.chars().count() > MAX_TITLE_LEN)
+    {
+        bot.send_message(
+            message.chat.id,
+            format!("Admin titles can't be longer than {MAX_TITLE_LEN} characters!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    // only hand out the rights the bot holds itself
+    let bot_member =
+        admin_cache::get_admin(bot, message.chat.id, UserId(*crate::BOT_ID as u64)).await?;
+
+    let Some(ChatMemberKind::Administrator(mut rights)) = bot_member.map(|m| m.kind) else {
+        return Err(anyhow!("Bot is not an admin"));
+    };
+
+    // nor the rights the caller doesn't hold, which the owner holds all of
+    match bot.get_chat_member(message.chat.id, caller.id).await?.kind {
+        ChatMemberKind::Owner(_) => {}
+        ChatMemberKind::Administrator(own) => {
+            rights.can_change_info &= own.can_change_info;
+            rights.can_delete_messages &= own.can_delete_messages;
+            rights.can_invite_users &= own.can_invite_users;
+            rights.can_restrict_members &= own.can_restrict_members;
+            rights.can_pin_messages &= own.can_pin_messages;
+            rights.can_manage_video_chats &= own.can_manage_video_chats;
+            rights.can_manage_chat &= own.can_manage_chat;
+            rights.can_manage_topics &= own.can_manage_topics;
+            rights.can_promote_members &= own.can_promote_members;
+        }
+        _ => return Err(anyhow!("User is not admin")),
+    }
+
+    let promotion = bot
+        .promote_chat_member(message.chat.id, member.user.id)
+        .can_change_info(rights.can_change_info)
+        .can_delete_messages(rights.can_delete_messages)
+        .can_invite_users(rights.can_invite_users)
+        .can_restrict_members(rights.can_restrict_members)
+        .can_pin_messages(rights.can_pin_messages)
+        .can_manage_video_chats(rights.can_manage_video_chats);
+
+    let promotion = if full {
+        promotion
+            .can_manage_chat(rights.can_manage_chat)
+            .can_manage_topics(rights.can_manage_topics)
+            .can_promote_members(rights.can_promote_members)
+    } else {
+        promotion
+    };
+
+    if let Err(e) = promotion.await {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "I couldn't promote {mention}: {}",
+                html::escape(&e.to_string())
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    if let Some(title) = &title {
+        bot.set_chat_administrator_custom_title(message.chat.id, member.user.id, title)
+            .await?;
+    }
+
+    log::info!(
+        "Promoted user {} in chat {}",
+        member.user.id,
+        message.chat.id
+    );
+
+    let mut entry = LogEntry::new(
+        LogCategory::Admin,
+        if full { "FULLPROMOTE" } else { "PROMOTE" },
+    )
+    .user(&member.user);
+    if let Some(title) = &title {
+        entry = entry.detail("Title", title);
+    }
+    entry.send(bot, message, pool).await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "{}promoted {mention}{}!",
+            if full { "Fully " } else { "Successfully " },
+            title
+                .map(|t| format!(" with the title {}", html::bold(&t)))
+                .unwrap_or_default()
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn promote(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    promote_member(bot, message, pool, false).await
+}
+
+pub async fn full_promote(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    promote_member(bot, message, pool, true).await
+}
+
+/// Checks that the target is an admin the bot is allowed to edit, ie. one it promoted itself.
+async fn can_edit_admin(
+    bot: &crate::types::TBot,
+    message: &Message,
+    member: &ChatMember,
+) -> anyhow::Result<bool> {
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+
+    let refusal = if member.is_owner() {
+        format!("{mention} owns this chat, I can't touch their rights!")
+    } else if !member.is_administrator() {
+        format!("{mention} isn't an admin!")
+    } else if !member.can_be_edited() {
+        format!("I didn't promote {mention}, so I can't edit their rights!")
+    } else {
+        return Ok(true);
+    };
+
+    bot.send_message(message.chat.id, refusal)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(false)
+}
+
+pub async fn demote(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, _)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !can_edit_admin(bot, message, &member).await? {
+        return Ok(());
+    }
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+
+    // promoting without any rights removes the admin status
+    if let Err(e) = bot
+        .promote_chat_member(message.chat.id, member.user.id)
+        .can_manage_chat(false)
+        .can_change_info(false)
+        .can_delete_messages(false)
+        .can_invite_users(false)
+        .can_restrict_members(false)
+        .can_pin_messages(false)
+        .can_manage_video_chats(false)
+        .can_manage_topics(false)
+        .can_promote_members(false)
+        .await
+    {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "I couldn't demote {mention}: {}",
+                html::escape(&e.to_string())
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    log::info!(
+        "Demoted user {} in chat {}",
+        member.user.id,
+        message.chat.id
+    );
+
+    LogEntry::new(LogCategory::Admin, "DEMOTE")
+        .user(&member.user)
+        .send(bot, message, pool)
+        .await;
+
+    bot.send_message(message.chat.id, format!("Demoted {mention}."))
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_title(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, title)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    let Some(title) = title else {
+        bot.send_message(message.chat.id, "You need to give me a title to set!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    };
+
+    if title.chars().count() > MAX_TITLE_LEN {
+        bot.send_message(
+            message.chat.id,
+            format!("Admin titles can't be longer than {MAX_TITLE_LEN} characters!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    if !can_edit_admin(bot, message, &member).await? {
+        return Ok(());
+    }
+
+    bot.set_chat_administrator_custom_title(message.chat.id, member.user.id, &title)
+        .await?;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Set the title of {} to {}.",
+            html::user_mention(member.user.id.0 as i64, &member.user.full_name()),
+            html::bold(&title)
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn refresh_admin_cache(
+    bot: &crate::types::TBot,
+    message: &Message,
+) -> anyhow::Result<()> {
+    let user = message.from().ok_or(anyhow!("User not found"))?;
+
+    // check the live status, the cached one might be what's outdated
+    let member = bot.get_chat_member(message.chat.id, user.id).await?;
+    if !member.is_privileged() {
+        bot.send_message(message.chat.id, "You need to be an admin for this to work!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let admins = admin_cache::refresh(bot, message.chat.id).await?;
+
+    bot.send_message(
+        message.chat.id,
+        format!("Refreshed the admin list, found {} admins.", admins.len()),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, MessageKind},
+};
+
+use crate::{
+    types::commands::UserCommands,
+    utils::perms::{self, Guard},
+};
+
+/// How long an anonymous admin has to verify themselves before the command is dropped.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+struct PendingCommand {
+    sent_at: Instant,
+    message: Message,
+    cmd: UserCommands,
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<HashMap<(ChatId, MessageId), PendingCommand>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Holds on to a command sent by an anonymous admin and asks them to verify who they are.
+pub async fn request_verification(
+    bot: &crate::types::TBot,
+    message: &Message,
+    cmd: UserCommands,
+) -> anyhow::Result<()> {
+    {
+        let mut pending = PENDING.lock().unwrap();
+        pending.retain(|_, p| p.sent_at.elapsed() < VERIFY_TIMEOUT);
+        pending.insert(
+            (message.chat.id, message.id),
+            PendingCommand {
+                sent_at: Instant::now(),
+                message: message.clone(),
+                cmd,
+            },
+        );
+    }
+
+    let keyboard = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+        "Verify you're an admin",
+        format!("anonadmin:{}", message.id.0),
+    )]]);
+
+    bot.send_message(
+        message.chat.id,
+        "You're posting anonymously, press the button below to verify that you're an admin.",
+    )
+    .reply_markup(keyboard)
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn verify_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+) -> anyhow::Result<()> {
+    let prompt = query
+        .message
+        .as_ref()
+        .ok_or(anyhow!("Unable to access callback message"))?;
+    let message_id = MessageId(data.parse()?);
+    let key = (prompt.chat.id, message_id);
+
+    // taken right away, so a second press can't run the command twice
+    let pending = PENDING.lock().unwrap().remove(&key);
+    let Some(pending) = pending.filter(|p| p.sent_at.elapsed() < VERIFY_TIMEOUT) else {
+        bot.answer_callback_query(&query.id)
+            .text("This command has expired, please send it again.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    };
+
+    // only an admin could have sent the command, whatever its own guards are
+    let guards = [&[Guard::UserAdmin], pending.cmd.guards()].concat();
+    let passes = perms::user_passes_guards(bot, prompt.chat.id, query.from.id, &guards).await;
+    let Ok(true) = passes else {
+        // another admin may still verify themselves
+        PENDING.lock().unwrap().insert(key, pending);
+        passes?;
+        bot.answer_callback_query(&query.id)
+            .text("You need to be an admin with the right rights to do this!")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    bot.delete_message(prompt.chat.id, prompt.id).await?;
+
+    // run the original command as the admin who verified themselves
+    let PendingCommand {
+        mut message, cmd, ..
+    } = pending;
+    if let MessageKind::Common(common) = &mut message.kind {
+        common.from = Some(query.from.clone());
+        common.sender_chat = None;
+    }
+
+    crate::user_cmd_handler(bot.clone(), message, cmd).await
+}
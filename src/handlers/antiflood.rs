@@ -0,0 +1,395 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration as StdDuration, Instant},
+};
+
+use chrono::Duration;
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+use teloxide::{prelude::*, types::MessageId, utils::html};
+
+use crate::{
+    handlers::{admin, approvals, log_channel::LogEntry, purge},
+    types::{db::FloodSettings, LogCategory, Punishment},
+    utils::{db, perms},
+};
+
+/// Flood limits outside of this range make little sense for a chat.
+const FLOOD_LIMIT_RANGE: std::ops::RangeInclusive<i32> = 1..=100;
+/// The longest window `/setfloodtimer` accepts, in seconds.
+const MAX_FLOOD_TIMER_SECS: i32 = 3600;
+
+/// Recent messages of a chat, used to detect floods.
+#[derive(Default)]
+struct ChatFlood {
+    /// The sender of the latest message and their uninterrupted streak of messages.
+    streak: Option<(UserId, Vec<MessageId>)>,
+    /// Messages of each user within the flood timer window.
+    recent: HashMap<UserId, VecDeque<(Instant, MessageId)>>,
+    /// The flood settings of the chat, so they aren't queried for every message.
+    settings: Option<Arc<FloodSettings>>,
+}
+
+lazy_static! {
+    static ref FLOOD_STATE: Mutex<HashMap<ChatId, ChatFlood>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the flood settings of a chat, from the cache if they were loaded before.
+async fn get_settings(
+    chat_id: ChatId,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Arc<FloodSettings>> {
+    let cached = FLOOD_STATE
+        .lock()
+        .unwrap()
+        .get(&chat_id)
+        .and_then(|chat| chat.settings.clone());
+    if let Some(settings) = cached {
+        return Ok(settings);
+    }
+
+    let settings = Arc::new(db::get_flood_settings(chat_id.0, pool).await?);
+    FLOOD_STATE
+        .lock()
+        .unwrap()
+        .entry(chat_id)
+        .or_default()
+        .settings = Some(settings.clone());
+
+    Ok(settings)
+}
+
+/// Drops the cached flood settings of a chat, for when they were changed.
+fn forget_settings(chat_id: ChatId) {
+    if let Some(chat) = FLOOD_STATE.lock().unwrap().get_mut(&chat_id) {
+        chat.settings = None;
+    }
+}
+
+/// Records a message, returning the messages making up a flood if it completes one.
+fn record_message(
+    chat_id: ChatId,
+    user_id: UserId,
+    message_id: MessageId,
+    settings: &FloodSettings,
+) -> Option<Vec<MessageId>> {
+    let mut state = FLOOD_STATE.lock().unwrap();
+    let chat = state.entry(chat_id).or_default();
+
+    match &mut chat.streak {
+        Some((last_user, messages)) if *last_user == user_id => messages.push(message_id),
+        streak => *streak = Some((user_id, vec![message_id])),
+    }
+
+    if settings.flood_timer_count > 0 {
+        chat.recent
+            .entry(user_id)
+            .or_default()
+            .push_back((Instant::now(), message_id));
+    }
+
+    // users that went quiet are forgotten, so the state doesn't keep everyone who ever talked
+    let window = StdDuration::from_secs(settings.flood_timer_secs.max(0) as u64);
+    chat.recent.retain(|_, recent| {
+        while recent
+            .front()
+            .is_some_and(|(sent_at, _)| sent_at.elapsed() > window)
+        {
+            recent.pop_front();
+        }
+        !recent.is_empty()
+    });
+
+    let streak_flood = chat
+        .streak
+        .as_ref()
+        .map(|(_, messages)| messages)
+        .filter(|messages| {
+            settings.flood_limit > 0 && messages.len() > settings.flood_limit as usize
+        })
+        .cloned();
+
+    let timer_flood = chat
+        .recent
+        .get(&user_id)
+        .filter(|recent| {
+            settings.flood_timer_count > 0 && recent.len() > settings.flood_timer_count as usize
+        })
+        .map(|recent| recent.iter().map(|(_, id)| *id).collect());
+
+    let flood = streak_flood.or(timer_flood);
+
+    // start counting from scratch once the user has been dealt with
+    if flood.is_some() {
+        chat.streak = None;
+        chat.recent.remove(&user_id);
+    }
+
+    flood
+}
+
+/// Checks a message for flooding and acts on it, returning whether it was part of a flood.
+pub async fn check_flood(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let Some(user) = message.from() else {
+        return Ok(false);
+    };
+
+    let settings = get_settings(message.chat.id, pool).await?;
+    if settings.flood_limit <= 0 && settings.flood_timer_count <= 0 {
+        return Ok(false);
+    }
+
+    let Some(flood) = record_message(message.chat.id, user.id, message.id, &settings) else {
+        return Ok(false);
+    };
+
+    // admins and approved users are free to talk as much as they like
+    if perms::is_user_admin(bot, message, user.id).await.is_ok()
+        || approvals::is_approved(message, pool).await?
+    {
+        return Ok(false);
+    }
+
+    let punishment: Punishment = settings.flood_mode.parse()?;
+    let duration = settings.flood_mode_duration.map(Duration::seconds);
+    let mention = html::user_mention(user.id.0 as i64, &user.full_name());
+
+    if let Err(e) = admin::punish(bot, message.chat.id, user.id, punishment, duration).await {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "{mention} is flooding the chat, but I couldn't {} them: {}",
+                punishment.as_str(),
+                html::escape(&e.to_string())
+            ),
+        )
+        .await?;
+        return Ok(true);
+    }
+
+    LogEntry::new(LogCategory::Automated, "FLOOD")
+        .user(user)
+        .detail("Action", admin::describe_punishment(punishment, duration))
+        .send(bot, message, pool)
+        .await;
+
+    if settings.clear_flood {
+        purge::delete_messages(bot, message.chat.id, flood).await;
+    }
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Yeah, I don't like your flooding. {mention} has been {}!",
+            admin::describe_punishment(punishment, duration)
+        ),
+    )
+    .await?;
+
+    Ok(true)
+}
+
+pub async fn flood(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let settings = db::get_flood_settings(message.chat.id.0, pool).await?;
+
+    if settings.flood_limit <= 0 && settings.flood_timer_count <= 0 {
+        bot.send_message(message.chat.id, "Antiflood is disabled in this chat.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let mut text = String::from("Antiflood settings for this chat:\n");
+    if settings.flood_limit > 0 {
+        text += &format!(
+            "- More than {} messages in a row trigger antiflood.\n",
+            settings.flood_limit
+        );
+    }
+    if settings.flood_timer_count > 0 {
+        text += &format!(
+            "- More than {} messages in {}s trigger antiflood.\n",
+            settings.flood_timer_count, settings.flood_timer_secs
+        );
+    }
+
+    let punishment: Punishment = settings.flood_mode.parse()?;
+    text += &format!(
+        "- Flooders will be {}.\n",
+        admin::describe_punishment(
+            punishment,
+            settings.flood_mode_duration.map(Duration::seconds)
+        )
+    );
+    if settings.clear_flood {
+        text += "- Flood messages will be deleted.\n";
+    }
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_flood(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("off" | "no" | "0") => {
+            db::set_flood_limit(message.chat.id.0, 0, pool).await?;
+            "Antiflood will no longer count consecutive messages.".to_owned()
+        }
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(limit) if FLOOD_LIMIT_RANGE.contains(&limit) => {
+                db::set_flood_limit(message.chat.id.0, limit, pool).await?;
+                format!("Antiflood will now act on more than {limit} consecutive messages.")
+            }
+            _ => format!(
+                "The flood limit has to be a number between {} and {}, or off!",
+                FLOOD_LIMIT_RANGE.start(),
+                FLOOD_LIMIT_RANGE.end()
+            ),
+        },
+        None => "You need to give me a number of messages, or off!".to_owned(),
+    };
+
+    forget_settings(message.chat.id);
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_flood_timer(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| t.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    let text = match args[..] {
+        ["off" | "no"] => {
+            db::set_flood_timer(message.chat.id.0, 0, 0, pool).await?;
+            "Antiflood will no longer count messages over time.".to_owned()
+        }
+        [count, secs] => match (count.parse::<i32>(), secs.parse::<i32>()) {
+            (Ok(count), Ok(secs))
+                if FLOOD_LIMIT_RANGE.contains(&count) && (1..=MAX_FLOOD_TIMER_SECS).contains(&secs) =>
+            {
+                db::set_flood_timer(message.chat.id.0, count, secs, pool).await?;
+                format!("Antiflood will now act on more than {count} messages in {secs}s.")
+            }
+            _ => format!(
+                "Give me a number of messages between {} and {}, and a number of seconds up to {MAX_FLOOD_TIMER_SECS}!",
+                FLOOD_LIMIT_RANGE.start(),
+                FLOOD_LIMIT_RANGE.end()
+            ),
+        },
+        _ => "Usage: /setfloodtimer <messages> <seconds>, or /setfloodtimer off".to_owned(),
+    };
+
+    forget_settings(message.chat.id);
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_flood_mode(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| t.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    let Some(mode) = args.first() else {
+        bot.send_message(
+            message.chat.id,
+            "You need to give me an action: ban, kick, mute, tban or tmute!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let text = match admin::parse_punishment(mode, args.get(1).copied()) {
+        Ok((punishment, duration)) => {
+            db::set_flood_mode(
+                message.chat.id.0,
+                punishment.as_str(),
+                duration.map(|d| d.num_seconds()),
+                pool,
+            )
+            .await?;
+            format!(
+                "Flooders will now be {}.",
+                admin::describe_punishment(punishment, duration)
+            )
+        }
+        Err(e) => e.to_string(),
+    };
+
+    forget_settings(message.chat.id);
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_clear_flood(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_clear_flood(message.chat.id.0, true, pool).await?;
+            "Flood messages will now be deleted."
+        }
+        Some("off" | "no") => {
+            db::set_clear_flood(message.chat.id.0, false, pool).await?;
+            "Flood messages will no longer be deleted."
+        }
+        _ => "Usage: /clearflood on|off",
+    };
+
+    forget_settings(message.chat.id);
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
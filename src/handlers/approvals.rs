@@ -0,0 +1,178 @@
+use anyhow::anyhow;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, log_channel::LogEntry},
+    types::LogCategory,
+    utils::{admin_cache, db, perms},
+};
+
+/// Whether the sender of a message is approved in the chat, and so skips its automated checks.
+pub async fn is_approved(message: &Message, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    match message.from() {
+        Some(user) => Ok(db::is_approved(message.chat.id.0, user.id.0 as i64, pool).await?),
+        None => Ok(false),
+    }
+}
+
+pub async fn approve(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let admin = message.from().ok_or(anyhow!("User not found"))?;
+    let Some((member, _)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+
+    let text = if perms::is_user_admin(bot, message, member.user.id)
+        .await
+        .is_ok()
+    {
+        format!("{mention} is an admin, they don't need to be approved.")
+    } else if db::approve_user(
+        message.chat.id.0,
+        member.user.id.0 as i64,
+        admin.id.0 as i64,
+        pool,
+    )
+    .await?
+    {
+        LogEntry::new(LogCategory::Admin, "APPROVE")
+            .user(&member.user)
+            .send(bot, message, pool)
+            .await;
+        format!("{mention} has been approved, locks and antiflood no longer apply to them.")
+    } else {
+        format!("{mention} is approved already.")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn unapprove(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, _)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+
+    let text = if db::unapprove_user(message.chat.id.0, member.user.id.0 as i64, pool).await? {
+        LogEntry::new(LogCategory::Admin, "UNAPPROVE")
+            .user(&member.user)
+            .send(bot, message, pool)
+            .await;
+        format!("{mention} is no longer approved.")
+    } else {
+        format!("{mention} isn't approved.")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn approved(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let users = db::get_approved_users(message.chat.id.0, pool).await?;
+
+    let text = if users.is_empty() {
+        "Nobody is approved in this chat.".to_owned()
+    } else {
+        let list = users
+            .iter()
+            .map(|(user_id, name)| {
+                let name = name.clone().unwrap_or_else(|| user_id.to_string());
+                format!("- {}", html::user_mention(*user_id, &name))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("These users are approved in this chat:\n{list}")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn unapprove_all(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("Yes, unapprove all", "unapproveall:confirm"),
+        InlineKeyboardButton::callback("Cancel", "unapproveall:cancel"),
+    ]]);
+
+    bot.send_message(
+        message.chat.id,
+        "Are you sure you want to unapprove all users in this chat?",
+    )
+    .reply_markup(keyboard)
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unapprove_all_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let message = query
+        .message
+        .as_ref()
+        .ok_or(anyhow!("Unable to access callback message"))?;
+
+    // only the owner could have run the command in the first place
+    let is_owner = admin_cache::get_admin(bot, message.chat.id, query.from.id)
+        .await?
+        .is_some_and(|m| m.is_owner());
+    if !is_owner {
+        bot.answer_callback_query(&query.id)
+            .text("Only the owner of this chat can do this!")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let text = match data {
+        "confirm" => {
+            let count = db::unapprove_all(message.chat.id.0, pool).await?;
+            LogEntry::new(LogCategory::Admin, "UNAPPROVEALL")
+                .admin(&query.from)
+                .detail("Unapproved", count.to_string())
+                .send(bot, message, pool)
+                .await;
+            format!("Unapproved {count} users.")
+        }
+        _ => "Unapproving all users was cancelled.".to_owned(),
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,394 @@
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
+use rand::{seq::SliceRandom, Rng};
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, MessageId, User},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, log_channel::LogEntry},
+    types::{db::PendingCaptcha, CaptchaMode, LogCategory},
+    utils::{db, time},
+};
+
+/// The longest time newcomers can be given to solve a captcha.
+const MAX_CAPTCHA_TIME_SECS: i64 = 24 * 60 * 60;
+/// Emojis the choice captcha picks from.
+const CHOICE_EMOJIS: [&str; 10] = ["🍎", "🚗", "🐶", "🌵", "⚽", "🎸", "🚀", "🌙", "🍕", "🐢"];
+/// How many buttons the math and choice captchas offer.
+const CHOICE_COUNT: usize = 6;
+
+/// Builds a challenge, returning its question, the options to pick from and the right answer.
+fn build_challenge(mode: CaptchaMode) -> (String, Vec<String>, String) {
+    let mut rng = rand::thread_rng();
+
+    match mode {
+        CaptchaMode::Button => (
+            "press the button below to prove you're human".to_owned(),
+            vec!["I'm not a robot".to_owned()],
+            "I'm not a robot".to_owned(),
+        ),
+        CaptchaMode::Math => {
+            let (a, b) = (rng.gen_range(1..=20), rng.gen_range(1..=20));
+            let answer = a + b;
+
+            let mut options = vec![answer];
+            while options.len() < CHOICE_COUNT {
+                let option = rng.gen_range(2..=40);
+                if !options.contains(&option) {
+                    options.push(option);
+                }
+            }
+            options.shuffle(&mut rng);
+
+            (
+                format!("what is {a} + {b}?"),
+                options.iter().map(ToString::to_string).collect(),
+                answer.to_string(),
+            )
+        }
+        CaptchaMode::Choice => {
+            let options: Vec<_> = CHOICE_EMOJIS
+                .choose_multiple(&mut rng, CHOICE_COUNT)
+                .map(|emoji| emoji.to_string())
+                .collect();
+            let answer = options.choose(&mut rng).cloned().unwrap_or_default();
+
+            (format!("tap the {answer} below"), options, answer)
+        }
+    }
+}
+
+fn captcha_keyboard(chat_id: ChatId, user_id: UserId, options: &[String]) -> InlineKeyboardMarkup {
+    let buttons: Vec<_> = options
+        .iter()
+        .map(|option| {
+            InlineKeyboardButton::callback(
+                option,
+                format!("captcha:{}:{}:{option}", chat_id.0, user_id.0),
+            )
+        })
+        .collect();
+
+    InlineKeyboardMarkup::new(buttons.chunks(3).map(|row| row.to_vec()))
+}
+
+/// Restricts a newcomer until they solve a captcha, kicking them if they don't in time.
+async fn challenge(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user: &User,
+    mode: CaptchaMode,
+    timeout: Duration,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    bot.restrict_chat_member(chat_id, user.id, ChatPermissions::empty())
+        .await?;
+
+    let (question, options, answer) = build_challenge(mode);
+    let sent = match bot
+        .send_message(
+            chat_id,
+            format!(
+                "Welcome {}! Before you can talk here, {question}\nYou have {} to solve this.",
+                html::user_mention(user.id.0 as i64, &user.full_name()),
+                time::format_duration(timeout)
+            ),
+        )
+        .reply_markup(captcha_keyboard(chat_id, user.id, &options))
+        .await
+    {
+        Ok(sent) => sent,
+        Err(e) => {
+            // without a captcha to solve the newcomer would stay muted for good
+            unrestrict(bot, chat_id, user.id).await?;
+            return Err(e.into());
+        }
+    };
+
+    db::add_pending_captcha(
+        &PendingCaptcha {
+            chat_id: chat_id.0,
+            user_id: user.id.0 as i64,
+            message_id: sent.id.0,
+            answer,
+            expires_at: Utc::now() + timeout,
+        },
+        pool,
+    )
+    .await?;
+
+    schedule_expiry(bot, chat_id, user.id, sent.id, timeout);
+
+    Ok(())
+}
+
+/// Kicks the user once their captcha expires, unless they solved it by then.
+fn schedule_expiry(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+    message_id: MessageId,
+    timeout: Duration,
+) {
+    let bot = bot.clone();
+    let timeout = timeout.to_std().unwrap_or_default();
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+
+        if let Err(e) = expire(&bot, chat_id, user_id, message_id).await {
+            log::warn!("Unable to remove {user_id} after their captcha expired: {e}");
+        }
+    });
+}
+
+async fn expire(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+    message_id: MessageId,
+) -> anyhow::Result<()> {
+    // a newer captcha for the same user has its own timeout
+    let pending =
+        db::remove_pending_captcha(chat_id.0, user_id.0 as i64, message_id.0, &crate::POOL).await?;
+    if !pending {
+        return Ok(());
+    }
+
+    let user = bot.get_chat_member(chat_id, user_id).await?.user;
+    fail(bot, chat_id, &user, message_id, "expired").await
+}
+
+/// Picks the pending captchas back up after a restart, removing the users whose captcha expired.
+pub async fn resume_pending(bot: &crate::types::TBot) -> anyhow::Result<()> {
+    for captcha in db::get_pending_captchas(&crate::POOL).await? {
+        schedule_expiry(
+            bot,
+            ChatId(captcha.chat_id),
+            UserId(captcha.user_id as u64),
+            MessageId(captcha.message_id),
+            captcha.expires_at - Utc::now(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Gives a user back the default permissions of the chat.
+async fn unrestrict(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+) -> anyhow::Result<()> {
+    let permissions = bot
+        .get_chat(chat_id)
+        .await?
+        .permissions()
+        .ok_or(anyhow!("Unable to access chat permissions"))?;
+    bot.restrict_chat_member(chat_id, user_id, permissions)
+        .await?;
+
+    Ok(())
+}
+
+/// Kicks a user that failed their captcha, cleaning up the captcha message.
+async fn fail(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user: &User,
+    message_id: MessageId,
+    cause: &str,
+) -> anyhow::Result<()> {
+    admin::kick_member(bot, chat_id, user.id).await?;
+
+    // the captcha may have been deleted by hand already
+    if let Err(e) = bot.delete_message(chat_id, message_id).await {
+        log::warn!("Unable to delete captcha in {chat_id}: {e}");
+    }
+
+    LogEntry::new(LogCategory::Automated, "CAPTCHA_FAILED")
+        .user(user)
+        .detail("Cause", cause)
+        .send_to_chat(bot, chat_id, None, &crate::POOL)
+        .await;
+
+    Ok(())
+}
+
+/// Challenges the users that joined a chat, if the chat has captchas on.
+pub async fn challenge_members(
+    bot: &crate::types::TBot,
+    message: &Message,
+    members: &[User],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let settings = db::get_captcha_settings(message.chat.id.0, pool).await?;
+    if !settings.captcha_enabled {
+        return Ok(());
+    }
+
+    let mode: CaptchaMode = settings.captcha_mode.parse()?;
+    let timeout = Duration::seconds(settings.captcha_time);
+
+    for user in members.iter().filter(|user| !user.is_bot) {
+        // the bot may lack the rights to restrict, which shouldn't stop the other newcomers
+        if let Err(e) = challenge(bot, message.chat.id, user, mode, timeout, pool).await {
+            log::warn!(
+                "Unable to challenge {} in {}: {e}",
+                user.id,
+                message.chat.id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn captcha_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+) -> anyhow::Result<()> {
+    let mut args = data.splitn(3, ':');
+    let (Some(chat_id), Some(user_id), Some(choice)) = (args.next(), args.next(), args.next())
+    else {
+        return Err(anyhow!("Malformed captcha callback: {data}"));
+    };
+    let chat_id = ChatId(chat_id.parse()?);
+    let user_id = UserId(user_id.parse()?);
+
+    if query.from.id != user_id {
+        bot.answer_callback_query(&query.id)
+            .text("This captcha isn't for you!")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let Some(captcha) = db::take_pending_captcha(chat_id.0, user_id.0 as i64, &crate::POOL).await?
+    else {
+        bot.answer_callback_query(&query.id)
+            .text("This captcha has expired.")
+            .await?;
+        return Ok(());
+    };
+
+    if captcha.answer != choice {
+        bot.answer_callback_query(&query.id)
+            .text("That's not right, try joining again later.")
+            .show_alert(true)
+            .await?;
+        return fail(
+            bot,
+            chat_id,
+            &query.from,
+            MessageId(captcha.message_id),
+            "wrong answer",
+        )
+        .await;
+    }
+
+    unrestrict(bot, chat_id, user_id).await?;
+
+    bot.answer_callback_query(&query.id)
+        .text("Thanks, you can talk now!")
+        .await?;
+    bot.delete_message(chat_id, MessageId(captcha.message_id))
+        .await?;
+
+    Ok(())
+}
+
+/// Shows whether captchas are on, or turns them on or off.
+pub async fn captcha(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_captcha_enabled(message.chat.id.0, true, pool).await?;
+            "Newcomers will now have to solve a captcha before they can talk.".to_owned()
+        }
+        Some("off" | "no") => {
+            db::set_captcha_enabled(message.chat.id.0, false, pool).await?;
+            "Newcomers will no longer have to solve a captcha.".to_owned()
+        }
+        Some(_) => "Usage: /captcha on|off".to_owned(),
+        None => {
+            let settings = db::get_captcha_settings(message.chat.id.0, pool).await?;
+            if settings.captcha_enabled {
+                format!(
+                    "Captchas are on, newcomers get a {} captcha and have {} to solve it.",
+                    settings.captcha_mode,
+                    time::format_duration(Duration::seconds(settings.captcha_time))
+                )
+            } else {
+                "Captchas are off in this chat.".to_owned()
+            }
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_captcha_mode(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg.map(str::parse::<CaptchaMode>) {
+        Some(Ok(mode)) => {
+            db::set_captcha_mode(message.chat.id.0, mode.as_str(), pool).await?;
+            format!("Newcomers will now get a {} captcha.", mode.as_str())
+        }
+        Some(Err(e)) => e.to_string(),
+        None => "You need to give me a captcha mode: button, math or choice!".to_owned(),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_captcha_time(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg.map(time::parse_duration) {
+        Some(Ok(duration)) if duration.num_seconds() <= MAX_CAPTCHA_TIME_SECS => {
+            db::set_captcha_time(message.chat.id.0, duration.num_seconds(), pool).await?;
+            format!(
+                "Newcomers will now have {} to solve their captcha.",
+                time::format_duration(duration)
+            )
+        }
+        Some(Ok(_)) => {
+            "Newcomers can't be given more than a day to solve their captcha!".to_owned()
+        }
+        Some(Err(e)) => e.to_string(),
+        None => "You need to give me a duration, eg. 5m or 1h!".to_owned(),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{prelude::*, types::MessageKind};
+
+use crate::{types::ServiceKind, utils::db};
+
+/// The kind of service message a message is, if it's one that can be cleaned up.
+fn service_kind(message: &Message) -> Option<ServiceKind> {
+    match &message.kind {
+        MessageKind::NewChatMembers(_) => Some(ServiceKind::Join),
+        MessageKind::LeftChatMember(_) => Some(ServiceKind::Leave),
+        MessageKind::Pinned(_) => Some(ServiceKind::Pin),
+        MessageKind::NewChatTitle(_) => Some(ServiceKind::Title),
+        MessageKind::NewChatPhoto(_) | MessageKind::DeleteChatPhoto(_) => Some(ServiceKind::Photo),
+        MessageKind::VideoChatScheduled(_)
+        | MessageKind::VideoChatStarted(_)
+        | MessageKind::VideoChatEnded(_)
+        | MessageKind::VideoChatParticipantsInvited(_) => Some(ServiceKind::VideoChat),
+        _ => None,
+    }
+}
+
+/// Deletes a service message if the chat asked for its kind to be cleaned up.
+pub async fn clean_service(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(kind) = service_kind(message) else {
+        return Ok(());
+    };
+
+    let kinds = db::get_clean_service(message.chat.id.0, pool).await?;
+    if !kinds.iter().any(|k| k == kind.as_str()) {
+        return Ok(());
+    }
+
+    // the bot may have lost its rights to delete messages
+    if let Err(e) = bot.delete_message(message.chat.id, message.id).await {
+        log::debug!(
+            "Unable to clean service message in {}: {e}",
+            message.chat.id
+        );
+    }
+
+    Ok(())
+}
+
+/// Shows which service messages are deleted, or picks them.
+pub async fn set_clean_service(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| t.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    let kinds: anyhow::Result<Vec<ServiceKind>> = match args[..] {
+        [] => {
+            let kinds = db::get_clean_service(message.chat.id.0, pool).await?;
+            let text = if kinds.is_empty() {
+                "No service messages are deleted in this chat.".to_owned()
+            } else {
+                format!(
+                    "These service messages are deleted in this chat: {}",
+                    kinds.join(", ")
+                )
+            };
+
+            bot.send_message(message.chat.id, text)
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+        ["on" | "yes" | "all"] => Ok(ServiceKind::ALL.to_vec()),
+        ["off" | "no" | "none"] => Ok(Vec::new()),
+        _ => args.iter().map(|arg| arg.parse()).collect(),
+    };
+
+    let text = match kinds {
+        Ok(kinds) => {
+            let mut names: Vec<_> = kinds.iter().map(|k| k.as_str().to_owned()).collect();
+            names.sort();
+            names.dedup();
+            db::set_clean_service(message.chat.id.0, &names, pool).await?;
+
+            if names.is_empty() {
+                "Service messages will no longer be deleted.".to_owned()
+            } else {
+                format!(
+                    "These service messages will now be deleted: {}",
+                    names.join(", ")
+                )
+            }
+        }
+        Err(e) => e.to_string(),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
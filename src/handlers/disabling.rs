@@ -0,0 +1,210 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{prelude::*, utils::html};
+
+use crate::{
+    types::commands::DISABLEABLE,
+    utils::{db, perms},
+};
+
+/// Whether a disableable command is disabled for the sender of a message.
+///
+/// The message is deleted when the chat asks for disabled commands to be deleted.
+pub async fn is_disabled(
+    bot: &crate::types::TBot,
+    message: &Message,
+    command: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let disabled = db::get_disabled_commands(message.chat.id.0, pool).await?;
+    if !disabled.commands.iter().any(|c| c == command) {
+        return Ok(false);
+    }
+
+    // disabled commands only apply to non-admins
+    if perms::is_anonymous_admin(message) {
+        return Ok(false);
+    }
+    if let Some(user) = message.from() {
+        if perms::is_user_admin(bot, message, user.id).await.is_ok() {
+            return Ok(false);
+        }
+    }
+
+    if disabled.delete_disabled {
+        if let Err(e) = bot.delete_message(message.chat.id, message.id).await {
+            log::warn!(
+                "Unable to delete disabled command in {}: {e}",
+                message.chat.id
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+/// Parses the commands given to `/disable` and `/enable`, dropping leading slashes.
+fn parse_command_args(message: &Message) -> anyhow::Result<Vec<String>> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| {
+            t.split_whitespace()
+                .skip(1)
+                .map(|arg| arg.trim_start_matches(['/', '#']).to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if args.is_empty() {
+        return Err(anyhow::anyhow!(
+            "You need to tell me which commands, use /disableable to see what can be disabled."
+        ));
+    }
+
+    if let Some(arg) = args.iter().find(|arg| !DISABLEABLE.contains(&arg.as_str())) {
+        return Err(anyhow::anyhow!(
+            "{arg} can't be disabled, use /disableable to see what can be disabled."
+        ));
+    }
+
+    Ok(args)
+}
+
+async fn update_disabled(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+    disable: bool,
+) -> anyhow::Result<()> {
+    let commands = match parse_command_args(message) {
+        Ok(commands) => commands,
+        Err(e) => {
+            bot.send_message(message.chat.id, html::escape(&e.to_string()))
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut disabled = db::get_disabled_commands(message.chat.id.0, pool)
+        .await?
+        .commands;
+    disabled.retain(|c| !commands.contains(c));
+    if disable {
+        disabled.extend(commands.iter().cloned());
+    }
+    disabled.sort();
+    disabled.dedup();
+    db::set_disabled_commands(message.chat.id.0, &disabled, pool).await?;
+
+    let names = commands.join(", ");
+    let text = if disable {
+        format!("Disabled {names} for non-admins.")
+    } else {
+        format!("Enabled {names}.")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn disable(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    update_disabled(bot, message, pool, true).await
+}
+
+pub async fn enable(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    update_disabled(bot, message, pool, false).await
+}
+
+pub async fn disabled(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let disabled = db::get_disabled_commands(message.chat.id.0, pool).await?;
+
+    let text = if disabled.commands.is_empty() {
+        "No commands are disabled in this chat.".to_owned()
+    } else {
+        let list = disabled
+            .commands
+            .iter()
+            .map(|c| format!("- {}", html::code_inline(c)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("These commands are disabled for non-admins:\n{list}")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn disableable(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let list = DISABLEABLE
+        .iter()
+        .map(|c| format!("- {}", html::code_inline(c)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "These commands can be disabled, <code>hashtag</code> being #note fetching:\n{list}"
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+/// Toggles deleting the messages of disabled commands.
+pub async fn disable_del(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_delete_disabled(message.chat.id.0, true, pool).await?;
+            "Disabled commands will now be deleted."
+        }
+        Some("off" | "no") => {
+            db::set_delete_disabled(message.chat.id.0, false, pool).await?;
+            "Disabled commands will no longer be deleted."
+        }
+        _ => {
+            let disabled = db::get_disabled_commands(message.chat.id.0, pool).await?;
+            if disabled.delete_disabled {
+                "Disabled commands are deleted. Use /disabledel off to keep them."
+            } else {
+                "Disabled commands are kept. Use /disabledel on to delete them."
+            }
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
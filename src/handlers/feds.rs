@@ -0,0 +1,593 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{InputFile, User},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, log_channel::LogEntry},
+    types::{db::Fed, LogCategory},
+    utils::db,
+};
+
+/// Federation names longer than this clutter every message they appear in.
+const MAX_FED_NAME_LEN: usize = 64;
+/// Ban lists longer than this are sent as a file instead.
+const MAX_LIST_LEN: usize = 4000;
+
+fn command_args(message: &Message) -> Option<&str> {
+    message
+        .text()
+        .and_then(|t| t.split_once(char::is_whitespace))
+        .map(|(_, args)| args.trim())
+        .filter(|args| !args.is_empty())
+}
+
+/// Resolves the federation a command is about: the one given by ID, the one of the chat,
+/// or the one the sender owns.
+async fn resolve_fed(message: &Message, pool: &Pool<Postgres>) -> anyhow::Result<Option<Fed>> {
+    if let Some(fed_id) = command_args(message) {
+        return db::get_fed(fed_id, pool).await;
+    }
+
+    if !message.chat.is_private() {
+        return db::get_chat_fed(message.chat.id.0, pool).await;
+    }
+
+    match message.from() {
+        Some(user) => db::get_fed_by_owner(user.id.0 as i64, pool).await,
+        None => Ok(None),
+    }
+}
+
+/// Returns the federation of the chat if the sender is one of its admins, replying otherwise.
+async fn require_fed_admin(
+    bot: &crate::types::TBot,
+    message: &Message,
+    fed: Option<Fed>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<Fed>> {
+    let user = message.from().ok_or(anyhow!("User not found"))?;
+
+    let refusal = match fed {
+        None => "I couldn't find that federation!",
+        Some(fed) if db::is_fed_admin(&fed, user.id.0 as i64, pool).await? => {
+            return Ok(Some(fed));
+        }
+        Some(_) => "Only federation admins can do this!",
+    };
+
+    bot.send_message(message.chat.id, refusal)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(None)
+}
+
+/// Bans a user in a chat on behalf of a federation, recording it so lifting the ban later
+/// leaves bans placed by the chat itself alone.
+async fn apply_fed_ban(
+    bot: &crate::types::TBot,
+    fed_id: &str,
+    chat_id: ChatId,
+    user_id: UserId,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let banned_already = bot
+        .get_chat_member(chat_id, user_id)
+        .await
+        .is_ok_and(|member| member.is_banned());
+    if banned_already {
+        return Ok(());
+    }
+
+    bot.ban_chat_member(chat_id, user_id).await?;
+    db::add_fed_ban_chat(fed_id, user_id.0 as i64, chat_id.0, pool).await?;
+
+    Ok(())
+}
+
+pub async fn new_fed(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let user = message.from().ok_or(anyhow!("User not found"))?;
+
+    let text = if let Some(fed) = db::get_fed_by_owner(user.id.0 as i64, pool).await? {
+        format!(
+            "You already own the federation {}, with the ID {}.",
+            html::bold(&fed.fed_name),
+            html::code_inline(&fed.fed_id)
+        )
+    } else {
+        match command_args(message) {
+            None => "You need to give your federation a name!".to_owned(),
+            Some(name) if name.chars().count() > MAX_FED_NAME_LEN => {
+                format!("Federation names can't be longer than {MAX_FED_NAME_LEN} characters!")
+            }
+            Some(name) => {
+                let fed = db::create_fed(name, user.id.0 as i64, pool).await?;
+                format!(
+                    "Created the federation {}, with the ID {}.\nUse /joinfed {} in your chats to add them to it.",
+                    html::bold(&fed.fed_name),
+                    html::code_inline(&fed.fed_id),
+                    html::escape(&fed.fed_id)
+                )
+            }
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn join_fed(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let fed = match command_args(message) {
+        Some(fed_id) => db::get_fed(fed_id, pool).await?,
+        None => None,
+    };
+
+    let text = match fed {
+        None => "You need to give me the ID of an existing federation!".to_owned(),
+        Some(fed) => {
+            db::join_fed(message.chat.id.0, &fed.fed_id, pool).await?;
+
+            // users banned before the chat joined may be in it already
+            let bans = db::get_fed_bans(&fed.fed_id, pool).await?;
+            let mut banned = 0;
+            for (ban, _) in &bans {
+                let user_id = UserId(ban.user_id as u64);
+                match apply_fed_ban(bot, &fed.fed_id, message.chat.id, user_id, pool).await {
+                    Ok(_) => banned += 1,
+                    Err(e) => log::warn!("Unable to fban {user_id} in {}: {e}", message.chat.id),
+                }
+            }
+
+            format!(
+                "This chat is now part of the federation {}, its bans apply here.{}",
+                html::bold(&fed.fed_name),
+                if bans.is_empty() {
+                    String::new()
+                } else {
+                    format!(" Applied {banned}/{} existing bans.", bans.len())
+                }
+            )
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn leave_fed(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let text = match db::get_chat_fed(message.chat.id.0, pool).await? {
+        Some(fed) => {
+            db::leave_fed(message.chat.id.0, pool).await?;
+            format!(
+                "This chat is no longer part of the federation {}.",
+                html::bold(&fed.fed_name)
+            )
+        }
+        None => "This chat isn't part of a federation!".to_owned(),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Makes a user an admin of the federation of the chat, for its owner only.
+pub async fn fed_promote(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let user = message.from().ok_or(anyhow!("User not found"))?;
+
+    let Some(fed) = db::get_chat_fed(message.chat.id.0, pool).await? else {
+        bot.send_message(message.chat.id, "This chat isn't part of a federation!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    };
+
+    if fed.owner_id != user.id.0 as i64 {
+        bot.send_message(
+            message.chat.id,
+            "Only the owner of the federation can promote its admins!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let Some((member, _)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+    let fed_name = html::bold(&fed.fed_name);
+
+    let text = if db::is_fed_admin(&fed, member.user.id.0 as i64, pool).await? {
+        format!("{mention} is an admin of {fed_name} already.")
+    } else {
+        db::add_fed_admin(&fed.fed_id, member.user.id.0 as i64, pool).await?;
+        format!("{mention} is now an admin of {fed_name}.")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Bans a user in every chat of the federation of the chat.
+pub async fn fed_ban(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let admin = message.from().ok_or(anyhow!("User not found"))?;
+    let fed = db::get_chat_fed(message.chat.id.0, pool).await?;
+    let Some(fed) = require_fed_admin(bot, message, fed, pool).await? else {
+        return Ok(());
+    };
+
+    let Some((member, reason)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+    let user_id = member.user.id.0 as i64;
+
+    let refusal = if user_id == *crate::BOT_ID {
+        Some("I'm not going to fban myself!")
+    } else if db::is_fed_admin(&fed, user_id, pool).await? {
+        Some("I can't fban an admin of the federation!")
+    } else {
+        None
+    };
+    if let Some(refusal) = refusal {
+        bot.send_message(message.chat.id, refusal)
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    db::fed_ban(
+        &fed.fed_id,
+        user_id,
+        reason.as_deref(),
+        admin.id.0 as i64,
+        pool,
+    )
+    .await?;
+
+    // the bot may have lost its rights in some of the chats
+    let chats = db::get_fed_chats(&fed.fed_id, pool).await?;
+    let mut banned = 0;
+    for (chat_id, _) in &chats {
+        match apply_fed_ban(bot, &fed.fed_id, ChatId(*chat_id), member.user.id, pool).await {
+            Ok(_) => banned += 1,
+            Err(e) => log::warn!("Unable to fban {user_id} in {chat_id}: {e}"),
+        }
+    }
+
+    log::info!("Fbanned user {user_id} in federation {}", fed.fed_id);
+
+    let mut entry = LogEntry::new(LogCategory::Admin, "FBAN")
+        .user(&member.user)
+        .detail("Federation", &fed.fed_name);
+    if let Some(reason) = &reason {
+        entry = entry.detail("Reason", reason);
+    }
+    entry.send(bot, message, pool).await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Banned {} in the federation {}, in {banned}/{} chats.{}",
+            html::user_mention(user_id, &member.user.full_name()),
+            html::bold(&fed.fed_name),
+            chats.len(),
+            admin::format_reason(reason.as_deref())
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fed_unban(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let fed = db::get_chat_fed(message.chat.id.0, pool).await?;
+    let Some(fed) = require_fed_admin(bot, message, fed, pool).await? else {
+        return Ok(());
+    };
+
+    let Some((member, _)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+    let user_id = member.user.id.0 as i64;
+    let mention = html::user_mention(user_id, &member.user.full_name());
+
+    // the records of where the ban was applied go with it
+    let chats = db::get_fed_ban_chats(&fed.fed_id, user_id, pool).await?;
+    if !db::fed_unban(&fed.fed_id, user_id, pool).await? {
+        bot.send_message(
+            message.chat.id,
+            format!("{mention} isn't banned in this federation!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    for chat_id in chats {
+        if let Err(e) = bot
+            .unban_chat_member(ChatId(chat_id), member.user.id)
+            .only_if_banned(true)
+            .await
+        {
+            log::warn!("Unable to unfban {user_id} in {chat_id}: {e}");
+        }
+    }
+
+    log::info!("Unfbanned user {user_id} in federation {}", fed.fed_id);
+
+    LogEntry::new(LogCategory::Admin, "UNFBAN")
+        .user(&member.user)
+        .detail("Federation", &fed.fed_name)
+        .send(bot, message, pool)
+        .await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "{mention} is no longer banned in the federation {}.",
+            html::bold(&fed.fed_name)
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fed_info(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(fed) = resolve_fed(message, pool).await? else {
+        bot.send_message(
+            message.chat.id,
+            "I couldn't find that federation! Give me its ID, or use this in a federated chat.",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let (admins, chats, bans) = db::get_fed_stats(&fed.fed_id, pool).await?;
+    let owner = db::get_user(Some(fed.owner_id), None, pool)
+        .await
+        .map(|u| u.full_name)
+        .unwrap_or_else(|_| fed.owner_id.to_string());
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "<b>Federation:</b> {}\n<b>ID:</b> {}\n<b>Owner:</b> {}\n<b>Admins:</b> {}\n<b>Chats:</b> {chats}\n<b>Bans:</b> {bans}",
+            html::escape(&fed.fed_name),
+            html::code_inline(&fed.fed_id),
+            html::user_mention(fed.owner_id, &owner),
+            admins + 1
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fed_chats(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let fed = resolve_fed(message, pool).await?;
+    let Some(fed) = require_fed_admin(bot, message, fed, pool).await? else {
+        return Ok(());
+    };
+
+    let chats = db::get_fed_chats(&fed.fed_id, pool).await?;
+
+    let text = if chats.is_empty() {
+        format!(
+            "No chats are part of the federation {} yet.",
+            html::bold(&fed.fed_name)
+        )
+    } else {
+        let list = chats
+            .iter()
+            .map(|(chat_id, name)| {
+                format!(
+                    "- {} ({})",
+                    html::escape(name.as_deref().unwrap_or("Unknown chat")),
+                    html::code_inline(&chat_id.to_string())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "These chats are part of the federation {}:\n{list}",
+            html::bold(&fed.fed_name)
+        )
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn fed_ban_list(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let fed = resolve_fed(message, pool).await?;
+    let Some(fed) = require_fed_admin(bot, message, fed, pool).await? else {
+        return Ok(());
+    };
+
+    let bans = db::get_fed_bans(&fed.fed_id, pool).await?;
+    if bans.is_empty() {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Nobody is banned in the federation {}.",
+                html::bold(&fed.fed_name)
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let list = bans
+        .iter()
+        .map(|(ban, name)| {
+            let name = name.clone().unwrap_or_else(|| ban.user_id.to_string());
+            format!(
+                "- {} ({}){}",
+                html::user_mention(ban.user_id, &name),
+                html::code_inline(&ban.user_id.to_string()),
+                ban.reason
+                    .as_deref()
+                    .map(|r| format!(": {}", html::escape(r)))
+                    .unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if list.len() <= MAX_LIST_LEN {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "These users are banned in the federation {}:\n{list}",
+                html::bold(&fed.fed_name)
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    // long lists don't fit in a message, so they are sent as plain text
+    let file = bans
+        .iter()
+        .map(|(ban, name)| {
+            format!(
+                "{}\t{}\t{}",
+                ban.user_id,
+                name.as_deref().unwrap_or_default(),
+                ban.reason.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bot.send_document(
+        message.chat.id,
+        InputFile::memory(file.into_bytes()).file_name("fbanlist.txt"),
+    )
+    .caption(format!(
+        "These {} users are banned in the federation {}.",
+        bans.len(),
+        html::bold(&fed.fed_name)
+    ))
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes newcomers that are banned in the federation of the chat,
+/// returning the ones that were removed.
+pub async fn check_new_members(
+    bot: &crate::types::TBot,
+    message: &Message,
+    members: &[User],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<HashSet<UserId>> {
+    let Some(fed) = db::get_chat_fed(message.chat.id.0, pool).await? else {
+        return Ok(HashSet::new());
+    };
+
+    let mut removed = HashSet::new();
+    for user in members {
+        let Some(ban) = db::get_fed_ban(&fed.fed_id, user.id.0 as i64, pool).await? else {
+            continue;
+        };
+
+        if let Err(e) = bot.ban_chat_member(message.chat.id, user.id).await {
+            log::warn!(
+                "Unable to remove fbanned {} from {}: {e}",
+                user.id,
+                message.chat.id
+            );
+            continue;
+        }
+        removed.insert(user.id);
+        db::add_fed_ban_chat(&fed.fed_id, user.id.0 as i64, message.chat.id.0, pool).await?;
+
+        let mut entry = LogEntry::new(LogCategory::Automated, "FBAN_JOIN")
+            .user(user)
+            .detail("Federation", &fed.fed_name);
+        if let Some(reason) = &ban.reason {
+            entry = entry.detail("Reason", reason);
+        }
+        entry.send(bot, message, pool).await;
+
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "{} is banned in the federation {}, so I removed them.{}",
+                html::user_mention(user.id.0 as i64, &user.full_name()),
+                html::bold(&fed.fed_name),
+                admin::format_reason(ban.reason.as_deref())
+            ),
+        )
+        .await?;
+    }
+
+    Ok(removed)
+}
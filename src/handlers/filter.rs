@@ -4,11 +4,12 @@ use teloxide::{payloads::SendMessageSetters, requests::Requester, types::Message
 use anyhow::anyhow;
 
 use crate::{
-    types::db::Note,
+    handlers::log_channel::LogEntry,
+    types::{db::Note, LogCategory},
     utils::{
         self,
         db::{self, insert_note},
-        perms,
+        formatting,
     },
 };
 
@@ -39,9 +40,14 @@ pub async fn get_note(
 
     let note = db::get_note(message.chat.id.0, note_id, pool).await?;
 
-    bot.send_message(message.chat.id, note.note_content)
-        .reply_to_message_id(message.id)
-        .await?;
+    formatting::send_content(
+        bot,
+        message.chat.id,
+        &note.note_content,
+        None,
+        Some(message.id),
+    )
+    .await?;
 
     Ok(())
 }
@@ -51,10 +57,6 @@ pub async fn save_note(
     message: &Message,
     pool: &Pool<Postgres>,
 ) -> anyhow::Result<()> {
-    tokio::try_join!(
-        perms::require_user_admin(bot, message), // user requires admin permissions
-    )?;
-
     let (_, text) = utils::extract_user_and_text(bot, message, pool).await;
     if text.is_none() {
         bot.send_message(message.chat.id, "You need to give the note a name!")
@@ -86,6 +88,11 @@ pub async fn save_note(
 
     match insert_note(&note, pool).await {
         Ok(_) => {
+            LogEntry::new(LogCategory::Notes, "NOTE_SAVED")
+                .detail("Note", note_id)
+                .send(bot, message, pool)
+                .await;
+
             bot.send_message(
                 message.chat.id,
                 format!("Saved note {}.", html::code_inline(note_id)),
@@ -118,7 +125,12 @@ pub async fn delete_note(
     }
 
     let note_id = text.ok_or(anyhow!("Unable to access message text"))?;
-    let _ = db::delete_note(message.chat.id.0, note_id.as_str(), pool).await?;
+    db::delete_note(message.chat.id.0, note_id.as_str(), pool).await?;
+
+    LogEntry::new(LogCategory::Notes, "NOTE_DELETED")
+        .detail("Note", &note_id)
+        .send(bot, message, pool)
+        .await;
 
     bot.send_message(
         message.chat.id,
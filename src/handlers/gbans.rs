@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{InputFile, User},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, log_channel::LogEntry},
+    types::LogCategory,
+    utils::{self, db},
+};
+
+/// Ban lists longer than this are sent as a file instead.
+const MAX_LIST_LEN: usize = 4000;
+
+/// Resolves the user a sudo command is aimed at along with the reason given for it,
+/// replying if no user could be found.
+///
+/// Sudo commands may be used in PM, so the user doesn't have to be part of the chat.
+async fn extract_target(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<(UserId, String, Option<String>)>> {
+    let (user_id, text) = utils::extract_user_and_text(bot, message, pool).await;
+
+    let Some(user_id) = user_id.map(UserId) else {
+        bot.send_message(
+            message.chat.id,
+            "You need to reply to a user, or give me their username or ID!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(None);
+    };
+
+    // a bare command used as a reply carries no reason of its own
+    let has_args = message
+        .text()
+        .is_some_and(|t| t.split_whitespace().count() > 1);
+    let reason = text.filter(|t| has_args && !t.trim().is_empty());
+
+    let name = db::get_user(Some(user_id.0 as i64), None, pool)
+        .await
+        .map(|u| u.full_name)
+        .unwrap_or_else(|_| user_id.to_string());
+
+    Ok(Some((user_id, name, reason)))
+}
+
+/// Bans a user from every chat the bot manages, apart from those that opted out.
+pub async fn gban(
+    bot: &crate::types::TBot,
+    message: &Message,
+    sudo: &[UserId],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let issuer = message.from().ok_or(anyhow!("User not found"))?;
+    let Some((user_id, name, reason)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    let refusal = if user_id.0 as i64 == *crate::BOT_ID {
+        Some("I'm not going to gban myself!")
+    } else if sudo.contains(&user_id) {
+        Some("I can't gban a sudo user!")
+    } else {
+        None
+    };
+    if let Some(refusal) = refusal {
+        bot.send_message(message.chat.id, refusal)
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    db::gban(
+        user_id.0 as i64,
+        reason.as_deref(),
+        issuer.id.0 as i64,
+        pool,
+    )
+    .await?;
+
+    // most chats won't have the user in them, which the ban covers all the same
+    let chats = db::get_antispam_chats(pool).await?;
+    let mut banned = 0;
+    for chat_id in chats.iter().map(|id| ChatId(*id)) {
+        // a ban the chat placed itself isn't the gban's to lift later
+        if bot
+            .get_chat_member(chat_id, user_id)
+            .await
+            .is_ok_and(|member| member.is_banned())
+        {
+            banned += 1;
+            continue;
+        }
+
+        if let Err(e) = bot.ban_chat_member(chat_id, user_id).await {
+            log::warn!("Unable to gban {user_id} in {chat_id}: {e}");
+            continue;
+        }
+        banned += 1;
+        db::add_gban_chat(user_id.0 as i64, chat_id.0, pool).await?;
+
+        let mut entry = LogEntry::new(LogCategory::Automated, "GBAN")
+            .detail("User", format!("{name} ({user_id})"));
+        if let Some(reason) = &reason {
+            entry = entry.detail("Reason", reason);
+        }
+        entry.send_to_chat(bot, chat_id, None, pool).await;
+    }
+
+    log::info!("Gbanned user {user_id}");
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Gbanned {}, in {banned}/{} chats.{}",
+            html::user_mention(user_id.0 as i64, &name),
+            chats.len(),
+            admin::format_reason(reason.as_deref())
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn ungban(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((user_id, name, _)) = extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+    let mention = html::user_mention(user_id.0 as i64, &name);
+
+    // bans placed by the chats themselves are left alone, the records go with the gban
+    let chats = db::get_gban_chats(user_id.0 as i64, pool).await?;
+    if !db::ungban(user_id.0 as i64, pool).await? {
+        bot.send_message(message.chat.id, format!("{mention} isn't gbanned!"))
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    for chat_id in chats {
+        if let Err(e) = bot
+            .unban_chat_member(ChatId(chat_id), user_id)
+            .only_if_banned(true)
+            .await
+        {
+            log::warn!("Unable to ungban {user_id} in {chat_id}: {e}");
+        }
+    }
+
+    log::info!("Ungbanned user {user_id}");
+
+    bot.send_message(message.chat.id, format!("{mention} is no longer gbanned."))
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn gban_list(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let gbans = db::get_gbans(pool).await?;
+    if gbans.is_empty() {
+        bot.send_message(message.chat.id, "Nobody is gbanned.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    let list = gbans
+        .iter()
+        .map(|(gban, name)| {
+            let name = name.clone().unwrap_or_else(|| gban.user_id.to_string());
+            format!(
+                "- {} ({}), {} by {}{}",
+                html::user_mention(gban.user_id, &name),
+                html::code_inline(&gban.user_id.to_string()),
+                gban.created_at.format("%Y-%m-%d"),
+                html::code_inline(&gban.banned_by.to_string()),
+                gban.reason
+                    .as_deref()
+                    .map(|r| format!(": {}", html::escape(r)))
+                    .unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if list.len() <= MAX_LIST_LEN {
+        bot.send_message(message.chat.id, format!("These users are gbanned:\n{list}"))
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    }
+
+    // long lists don't fit in a message, so they are sent as plain text
+    let file = gbans
+        .iter()
+        .map(|(gban, name)| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}",
+                gban.user_id,
+                name.as_deref().unwrap_or_default(),
+                gban.created_at.to_rfc3339(),
+                gban.banned_by,
+                gban.reason.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bot.send_document(
+        message.chat.id,
+        InputFile::memory(file.into_bytes()).file_name("gbanlist.txt"),
+    )
+    .caption(format!("These {} users are gbanned.", gbans.len()))
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+/// Toggles whether global bans apply to the chat.
+pub async fn antispam(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_antispam(message.chat.id.0, true, pool).await?;
+            "Gbanned users will now be removed from this chat."
+        }
+        Some("off" | "no") => {
+            db::set_antispam(message.chat.id.0, false, pool).await?;
+            "Gbanned users will no longer be removed from this chat."
+        }
+        _ => {
+            if db::get_antispam(message.chat.id.0, pool).await? {
+                "Gbanned users are removed from this chat. Use /antispam off to let them in."
+            } else {
+                "Gbanned users are let in to this chat. Use /antispam on to remove them."
+            }
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Removes gbanned newcomers unless the chat opted out, returning the ones that were removed.
+pub async fn check_new_members(
+    bot: &crate::types::TBot,
+    message: &Message,
+    members: &[User],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<HashSet<UserId>> {
+    if !db::get_antispam(message.chat.id.0, pool).await? {
+        return Ok(HashSet::new());
+    }
+
+    let mut removed = HashSet::new();
+    for user in members {
+        let Some(gban) = db::get_gban(user.id.0 as i64, pool).await? else {
+            continue;
+        };
+
+        if let Err(e) = bot.ban_chat_member(message.chat.id, user.id).await {
+            log::warn!(
+                "Unable to remove gbanned {} from {}: {e}",
+                user.id,
+                message.chat.id
+            );
+            continue;
+        }
+        removed.insert(user.id);
+        db::add_gban_chat(user.id.0 as i64, message.chat.id.0, pool).await?;
+
+        let mut entry = LogEntry::new(LogCategory::Automated, "GBAN_JOIN").user(user);
+        if let Some(reason) = &gban.reason {
+            entry = entry.detail("Reason", reason);
+        }
+        entry.send(bot, message, pool).await;
+
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "{} is gbanned, so I removed them.{}",
+                html::user_mention(user.id.0 as i64, &user.full_name()),
+                admin::format_reason(gban.reason.as_deref())
+            ),
+        )
+        .await?;
+    }
+
+    Ok(removed)
+}
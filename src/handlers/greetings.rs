@@ -0,0 +1,447 @@
+use anyhow::anyhow;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{Chat, MessageId, User},
+    utils::html,
+};
+
+use crate::{
+    handlers::rules,
+    types::{db::Greetings, MediaKind},
+    utils::{db, formatting},
+};
+
+/// Sent to newcomers in chats that didn't set a welcome of their own.
+const DEFAULT_WELCOME: &str = "Hey there {first}, and welcome to {chatname}! How are you?";
+
+/// Sent to members leaving chats that didn't set a goodbye of their own.
+const DEFAULT_GOODBYE: &str = "Nice knowing you, {first}!";
+
+/// The text and media of a stored greeting, falling back to a default text.
+fn stored_content<'a>(
+    text: &'a Option<String>,
+    media_kind: &'a Option<String>,
+    file_id: &'a Option<String>,
+    default: &'static str,
+) -> anyhow::Result<(&'a str, Option<(MediaKind, &'a str)>)> {
+    let media = match (media_kind, file_id) {
+        (Some(kind), Some(file_id)) => Some((kind.parse()?, file_id.as_str())),
+        _ => None,
+    };
+
+    Ok((text.as_deref().unwrap_or(default), media))
+}
+
+fn welcome_content(greetings: &Greetings) -> anyhow::Result<(&str, Option<(MediaKind, &str)>)> {
+    stored_content(
+        &greetings.welcome_text,
+        &greetings.welcome_media_kind,
+        &greetings.welcome_file_id,
+        DEFAULT_WELCOME,
+    )
+}
+
+fn goodbye_content(greetings: &Greetings) -> anyhow::Result<(&str, Option<(MediaKind, &str)>)> {
+    stored_content(
+        &greetings.goodbye_text,
+        &greetings.goodbye_media_kind,
+        &greetings.goodbye_file_id,
+        DEFAULT_GOODBYE,
+    )
+}
+
+/// Fills in a greeting for a user, linking to the rules of the chat if it asks to.
+async fn render(
+    bot: &crate::types::TBot,
+    text: &str,
+    user: &User,
+    chat: &Chat,
+) -> anyhow::Result<String> {
+    let text = formatting::fill(text, user, chat);
+    rules::fill_rules_button(bot, chat.id, &text).await
+}
+
+/// Reads a new greeting from the command text, or from the replied message.
+///
+/// The greeting is previewed to check that Telegram accepts its formatting, `None` is returned
+/// once the user has been told what went wrong.
+async fn read_greeting<'a>(
+    bot: &crate::types::TBot,
+    message: &'a Message,
+    name: &str,
+) -> anyhow::Result<Option<(&'a str, Option<(MediaKind, String)>)>> {
+    let user = message.from().ok_or(anyhow!("User not found"))?;
+    let args = message
+        .text()
+        .and_then(|t| t.split_once(char::is_whitespace))
+        .map(|(_, args)| args.trim())
+        .filter(|args| !args.is_empty());
+
+    let reply = message.reply_to_message();
+    let media = reply.and_then(formatting::extract_media);
+    let text = args
+        .or_else(|| reply.and_then(|r| r.text().or(r.caption())))
+        .unwrap_or_default();
+
+    if text.is_empty() && media.is_none() {
+        bot.send_message(
+            message.chat.id,
+            format!("You need to give me a {name}, or reply to a message to use it as the {name}!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(None);
+    }
+
+    let preview = formatting::send_content(
+        bot,
+        message.chat.id,
+        &render(bot, text, user, &message.chat).await?,
+        media.as_ref().map(|(kind, id)| (*kind, id.as_str())),
+        Some(message.id),
+    )
+    .await;
+
+    if let Err(e) = preview {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "I couldn't send that {name}, so it wasn't saved: {}",
+                html::escape(&e.to_string())
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(None);
+    }
+
+    Ok(Some((text, media)))
+}
+
+/// Greets the users that joined a chat with its welcome.
+pub async fn welcome_members(
+    bot: &crate::types::TBot,
+    message: &Message,
+    members: &[User],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let greetings = db::get_greetings(message.chat.id.0, pool).await?;
+    if !greetings.welcome_enabled {
+        return Ok(());
+    }
+
+    let (text, media) = welcome_content(&greetings)?;
+    let mut previous = greetings.last_welcome_id.map(MessageId);
+
+    for user in members.iter().filter(|user| !user.is_bot) {
+        let text = render(bot, text, user, &message.chat).await?;
+        let sent = formatting::send_content(bot, message.chat.id, &text, media, None).await?;
+
+        // the welcome may have been deleted by hand already
+        if let (true, Some(previous)) = (greetings.clean_welcome, previous) {
+            if let Err(e) = bot.delete_message(message.chat.id, previous).await {
+                log::debug!("Unable to delete previous welcome: {e}");
+            }
+        }
+        previous = Some(sent.id);
+    }
+
+    if previous != greetings.last_welcome_id.map(MessageId) {
+        db::set_last_welcome_id(message.chat.id.0, previous.map(|id| id.0), pool).await?;
+    }
+
+    Ok(())
+}
+
+/// Shows the welcome settings of a chat, or turns welcomes on or off.
+pub async fn welcome(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_welcome_enabled(message.chat.id.0, true, pool).await?;
+            "New members will now be welcomed."
+        }
+        Some("off" | "no") => {
+            db::set_welcome_enabled(message.chat.id.0, false, pool).await?;
+            "New members will no longer be welcomed."
+        }
+        Some(_) => "Usage: /welcome on|off, or just /welcome to see the current welcome.",
+        None => {
+            let greetings = db::get_greetings(message.chat.id.0, pool).await?;
+            let user = message.from().ok_or(anyhow!("User not found"))?;
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Welcomes are {} and previous welcomes {} deleted. The welcome looks like this:",
+                    if greetings.welcome_enabled { "on" } else { "off" },
+                    if greetings.clean_welcome { "are" } else { "aren't" },
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+
+            let (text, media) = welcome_content(&greetings)?;
+            formatting::send_content(
+                bot,
+                message.chat.id,
+                &render(bot, text, user, &message.chat).await?,
+                media,
+                None,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Sets the welcome of a chat from the command text, or from the replied message.
+pub async fn set_welcome(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((text, media)) = read_greeting(bot, message, "welcome").await? else {
+        return Ok(());
+    };
+
+    db::set_welcome(
+        message.chat.id.0,
+        Some(text),
+        media.as_ref().map(|(kind, _)| kind.as_str()),
+        media.as_ref().map(|(_, id)| id.as_str()),
+        pool,
+    )
+    .await?;
+
+    bot.send_message(
+        message.chat.id,
+        "Saved the new welcome, it looks like the above.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn reset_welcome(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    db::set_welcome(message.chat.id.0, None, None, None, pool).await?;
+
+    bot.send_message(
+        message.chat.id,
+        "The welcome has been reset to the default one.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+/// Toggles deleting the previous welcome whenever a new one is sent.
+pub async fn clean_welcome(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_clean_welcome(message.chat.id.0, true, pool).await?;
+            "Previous welcomes will now be deleted when someone new joins."
+        }
+        Some("off" | "no") => {
+            db::set_clean_welcome(message.chat.id.0, false, pool).await?;
+            "Previous welcomes will no longer be deleted."
+        }
+        _ => "Usage: /cleanwelcome on|off",
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Says goodbye to a user that left the chat.
+pub async fn goodbye_member(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(user) = message.left_chat_member() else {
+        return Ok(());
+    };
+
+    if user.is_bot {
+        return Ok(());
+    }
+
+    let greetings = db::get_greetings(message.chat.id.0, pool).await?;
+    if !greetings.goodbye_enabled {
+        return Ok(());
+    }
+
+    // users removed by the bot already got a message about why they're gone
+    let removed_by_bot = message
+        .from()
+        .is_some_and(|from| from.id.0 as i64 == *crate::BOT_ID);
+    if removed_by_bot && !greetings.goodbye_kicked {
+        return Ok(());
+    }
+
+    let (text, media) = goodbye_content(&greetings)?;
+    formatting::send_content(
+        bot,
+        message.chat.id,
+        &render(bot, text, user, &message.chat).await?,
+        media,
+        None,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Shows the goodbye settings of a chat, or turns goodbyes on or off.
+pub async fn goodbye(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_goodbye_enabled(message.chat.id.0, true, pool).await?;
+            "Leaving members will now get a goodbye."
+        }
+        Some("off" | "no") => {
+            db::set_goodbye_enabled(message.chat.id.0, false, pool).await?;
+            "Leaving members will no longer get a goodbye."
+        }
+        Some(_) => "Usage: /goodbye on|off, or just /goodbye to see the current goodbye.",
+        None => {
+            let greetings = db::get_greetings(message.chat.id.0, pool).await?;
+            let user = message.from().ok_or(anyhow!("User not found"))?;
+
+            bot.send_message(
+                message.chat.id,
+                format!(
+                    "Goodbyes are {}, and {} sent for users I removed. The goodbye looks like this:",
+                    if greetings.goodbye_enabled { "on" } else { "off" },
+                    if greetings.goodbye_kicked { "are" } else { "aren't" },
+                ),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+
+            let (text, media) = goodbye_content(&greetings)?;
+            formatting::send_content(
+                bot,
+                message.chat.id,
+                &render(bot, text, user, &message.chat).await?,
+                media,
+                None,
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Sets the goodbye of a chat from the command text, or from the replied message.
+pub async fn set_goodbye(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((text, media)) = read_greeting(bot, message, "goodbye").await? else {
+        return Ok(());
+    };
+
+    db::set_goodbye(
+        message.chat.id.0,
+        Some(text),
+        media.as_ref().map(|(kind, _)| kind.as_str()),
+        media.as_ref().map(|(_, id)| id.as_str()),
+        pool,
+    )
+    .await?;
+
+    bot.send_message(
+        message.chat.id,
+        "Saved the new goodbye, it looks like the above.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn reset_goodbye(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    db::set_goodbye(message.chat.id.0, None, None, None, pool).await?;
+
+    bot.send_message(
+        message.chat.id,
+        "The goodbye has been reset to the default one.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+/// Toggles goodbyes for users that were kicked or banned by the bot.
+pub async fn goodbye_kicked(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_goodbye_kicked(message.chat.id.0, true, pool).await?;
+            "Users I kick or ban will get a goodbye as well."
+        }
+        Some("off" | "no") => {
+            db::set_goodbye_kicked(message.chat.id.0, false, pool).await?;
+            "Users I kick or ban will no longer get a goodbye."
+        }
+        _ => "Usage: /goodbyekicked on|off",
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,496 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{ChatJoinRequest, InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::{
+    handlers::log_channel::LogEntry,
+    types::{db::User, JoinMode, LogCategory},
+    utils::db,
+};
+
+/// How long a user has to verify themselves before they have to request to join again.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// Keeps every question along with its answer within a single log message.
+const MAX_QUESTION_LEN: usize = 500;
+/// More questions than this would scare anyone away.
+const MAX_QUESTIONS: usize = 10;
+/// Prefix of the `/start` payload that starts answering the join questions of a chat.
+const ANSWERS_PAYLOAD: &str = "join_";
+
+/// A join request waiting for the user to verify themselves by PM.
+#[derive(Clone)]
+struct PendingRequest {
+    requested_at: Instant,
+    chat_id: ChatId,
+    chat_title: String,
+    /// Where the answers are sent, they are kept private unless a chat was set for them.
+    log_chat: Option<ChatId>,
+    questions: Vec<String>,
+    answers: Vec<String>,
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<HashMap<(ChatId, UserId), PendingRequest>> =
+        Mutex::new(HashMap::new());
+    /// The chat whose questions each user is answering, as they may be asking to join several.
+    static ref ANSWERING: Mutex<HashMap<UserId, ChatId>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the chat whose join questions a `/start` payload asks for, if it does.
+pub fn answers_payload(payload: &str) -> Option<ChatId> {
+    payload
+        .strip_prefix(ANSWERS_PAYLOAD)
+        .and_then(|id| id.parse().ok())
+        .map(ChatId)
+}
+
+/// Handles a join request according to the join mode of the chat.
+pub async fn join_request_handler(
+    bot: crate::types::TBot,
+    request: ChatJoinRequest,
+) -> anyhow::Result<()> {
+    let pool = &crate::POOL;
+    let user = &request.from;
+
+    // users asking to join are likely to be the target of admin commands later on
+    db::insert_user(
+        &User {
+            user_id: user.id.0 as i64,
+            user_name: user.username.as_ref().map(|s| s.to_lowercase()),
+            full_name: user.full_name(),
+        },
+        pool,
+    )
+    .await?;
+
+    let settings = db::get_join_settings(request.chat.id.0, pool).await?;
+    let mode: JoinMode = settings.join_mode.parse()?;
+
+    match mode {
+        JoinMode::Manual => {}
+        JoinMode::Approve => {
+            bot.approve_chat_join_request(request.chat.id, user.id)
+                .await?;
+
+            LogEntry::new(LogCategory::Automated, "JOIN_APPROVED")
+                .user(user)
+                .send_to_chat(&bot, request.chat.id, None, pool)
+                .await;
+        }
+        JoinMode::Decline => {
+            bot.decline_chat_join_request(request.chat.id, user.id)
+                .await?;
+
+            LogEntry::new(LogCategory::Automated, "JOIN_DECLINED")
+                .user(user)
+                .send_to_chat(&bot, request.chat.id, None, pool)
+                .await;
+        }
+        JoinMode::Verify => {
+            let pending = PendingRequest {
+                requested_at: Instant::now(),
+                chat_id: request.chat.id,
+                chat_title: request.chat.title().unwrap_or("the chat").to_owned(),
+                log_chat: settings.join_log_chat.map(ChatId),
+                questions: settings.join_questions,
+                answers: Vec::new(),
+            };
+
+            let title = html::escape(&pending.chat_title);
+            let res = if pending.questions.is_empty() {
+                let keyboard = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+                    "I'm not a robot",
+                    format!("joinreq:{}", request.chat.id.0),
+                )]]);
+                bot.send_message(
+                    ChatId(user.id.0 as i64),
+                    format!("Press the button below to join {title}."),
+                )
+                .reply_markup(keyboard)
+                .await
+            } else {
+                // the link names the chat, so the answers can't end up with another request
+                let me = bot.get_me().await?;
+                let link = format!(
+                    "https://t.me/{}?start={ANSWERS_PAYLOAD}{}",
+                    me.username(),
+                    request.chat.id.0
+                );
+                let keyboard = InlineKeyboardMarkup::new([[InlineKeyboardButton::url(
+                    "Answer the questions",
+                    link.parse()?,
+                )]]);
+                bot.send_message(
+                    ChatId(user.id.0 as i64),
+                    format!(
+                        "Before you can join {title}, please answer a few questions. Press the button below to start."
+                    ),
+                )
+                .reply_markup(keyboard)
+                .await
+            };
+
+            // the request stays open for the admins if the user can't be reached
+            if let Err(e) = res {
+                log::warn!("Unable to send join verification to {}: {e}", user.id);
+                return Ok(());
+            }
+
+            let mut pending_requests = PENDING.lock().unwrap();
+            pending_requests.retain(|_, p| p.requested_at.elapsed() < VERIFY_TIMEOUT);
+            pending_requests.insert((request.chat.id, user.id), pending);
+        }
+    };
+
+    Ok(())
+}
+
+/// Starts asking the join questions of a chat by PM, for users following the link to them.
+pub async fn start_answers(
+    bot: &crate::types::TBot,
+    message: &Message,
+    chat_id: ChatId,
+) -> anyhow::Result<()> {
+    let Some(user) = message.from() else {
+        return Ok(());
+    };
+
+    let first_question = {
+        let mut pending_requests = PENDING.lock().unwrap();
+        pending_requests
+            .get_mut(&(chat_id, user.id))
+            .filter(|p| p.requested_at.elapsed() < VERIFY_TIMEOUT)
+            .and_then(|pending| {
+                // following the link again starts over
+                pending.answers.clear();
+                pending.questions.first().cloned()
+            })
+    };
+
+    let Some(question) = first_question else {
+        bot.send_message(
+            message.chat.id,
+            "This request has expired, please ask to join again.",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    ANSWERING.lock().unwrap().insert(user.id, chat_id);
+    bot.send_message(message.chat.id, html::escape(&question))
+        .await?;
+
+    Ok(())
+}
+
+/// Records an answer to the join questions sent by PM, returning whether the message was one.
+pub async fn record_answer(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<bool> {
+    let (Some(user), Some(answer)) = (message.from(), message.text()) else {
+        return Ok(false);
+    };
+
+    let Some(chat_id) = ANSWERING.lock().unwrap().get(&user.id).copied() else {
+        return Ok(false);
+    };
+    let key = (chat_id, user.id);
+
+    let (next_question, finished) = {
+        let mut pending_requests = PENDING.lock().unwrap();
+        let Some(pending) = pending_requests
+            .get_mut(&key)
+            .filter(|p| !p.questions.is_empty() && p.requested_at.elapsed() < VERIFY_TIMEOUT)
+        else {
+            ANSWERING.lock().unwrap().remove(&user.id);
+            return Ok(false);
+        };
+
+        pending.answers.push(answer.to_owned());
+        let next_question = pending.questions.get(pending.answers.len()).cloned();
+        let finished = match next_question {
+            Some(_) => None,
+            None => pending_requests.remove(&key),
+        };
+        (next_question, finished)
+    };
+
+    if let Some(question) = next_question {
+        bot.send_message(message.chat.id, html::escape(&question))
+            .await?;
+        return Ok(true);
+    }
+    let pending = finished.ok_or(anyhow!("Join request vanished while answering"))?;
+    ANSWERING.lock().unwrap().remove(&user.id);
+
+    bot.approve_chat_join_request(pending.chat_id, user.id)
+        .await?;
+
+    LogEntry::new(LogCategory::Automated, "JOIN_VERIFIED")
+        .user(user)
+        .send_to_chat(bot, pending.chat_id, None, &crate::POOL)
+        .await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Thanks for answering, you've been let in to {}!",
+            html::escape(&pending.chat_title)
+        ),
+    )
+    .await?;
+
+    let Some(log_chat) = pending.log_chat else {
+        return Ok(true);
+    };
+
+    let answers = pending
+        .questions
+        .iter()
+        .zip(&pending.answers)
+        .map(|(q, a)| format!("<b>{}</b>\n{}\n", html::escape(q), html::escape(a)))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bot.send_message(
+        log_chat,
+        format!(
+            "{} joined {} after answering the join questions:\n\n{answers}",
+            html::user_mention(user.id.0 as i64, &user.full_name()),
+            html::escape(&pending.chat_title)
+        ),
+    )
+    .await?;
+
+    Ok(true)
+}
+
+pub async fn join_request_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+) -> anyhow::Result<()> {
+    let chat_id = ChatId(data.parse()?);
+
+    let pending = {
+        let mut pending_requests = PENDING.lock().unwrap();
+        pending_requests
+            .remove(&(chat_id, query.from.id))
+            .filter(|p| p.requested_at.elapsed() < VERIFY_TIMEOUT)
+    };
+
+    let Some(pending) = pending else {
+        bot.answer_callback_query(&query.id)
+            .text("This request has expired, please ask to join again.")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    };
+
+    bot.approve_chat_join_request(chat_id, query.from.id)
+        .await?;
+
+    LogEntry::new(LogCategory::Automated, "JOIN_VERIFIED")
+        .user(&query.from)
+        .send_to_chat(bot, chat_id, None, &crate::POOL)
+        .await;
+    bot.answer_callback_query(&query.id).await?;
+
+    if let Some(message) = &query.message {
+        bot.edit_message_text(
+            message.chat.id,
+            message.id,
+            format!(
+                "You've been let in to {}!",
+                html::escape(&pending.chat_title)
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Shows how join requests are handled, or changes it.
+pub async fn join_requests(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg.map(str::parse::<JoinMode>) {
+        Some(Ok(mode)) => {
+            db::set_join_mode(message.chat.id.0, mode.as_str(), pool).await?;
+            match mode {
+                JoinMode::Manual => "Join requests are now left for the admins.",
+                JoinMode::Approve => "Join requests will now be approved right away.",
+                JoinMode::Decline => "Join requests will now be declined right away.",
+                JoinMode::Verify => {
+                    "Users asking to join will now have to verify themselves by PM first."
+                }
+            }
+            .to_owned()
+        }
+        Some(Err(e)) => e.to_string(),
+        None => {
+            let settings = db::get_join_settings(message.chat.id.0, pool).await?;
+            format!(
+                "Join requests are handled in {} mode, with {} join questions.",
+                settings.join_mode,
+                settings.join_questions.len()
+            )
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Sets the questions users have to answer in verify mode, one per line.
+pub async fn set_join_questions(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args = message
+        .text()
+        .and_then(|t| t.split_once(char::is_whitespace))
+        .map(|(_, args)| args.trim())
+        .unwrap_or_default();
+
+    let questions: Vec<_> = args
+        .lines()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(ToOwned::to_owned)
+        .collect();
+
+    let text = match &questions[..] {
+        [] => {
+            let settings = db::get_join_settings(message.chat.id.0, pool).await?;
+            if settings.join_questions.is_empty() {
+                "There are no join questions, users only have to press a button to verify themselves. Give me some questions, one per line, to ask them instead.".to_owned()
+            } else {
+                let list = settings
+                    .join_questions
+                    .iter()
+                    .map(|q| format!("- {}", html::escape(q)))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("Users asking to join are asked:\n{list}")
+            }
+        }
+        [off] if matches!(off.as_str(), "off" | "no") => {
+            db::set_join_questions(message.chat.id.0, &[], pool).await?;
+            "Cleared the join questions, users only have to press a button to verify themselves."
+                .to_owned()
+        }
+        _ if questions.len() > MAX_QUESTIONS => {
+            format!("You can't ask more than {MAX_QUESTIONS} questions!")
+        }
+        _ if questions.iter().any(|q| q.len() > MAX_QUESTION_LEN) => {
+            format!("Questions can't be longer than {MAX_QUESTION_LEN} characters!")
+        }
+        _ => {
+            db::set_join_questions(message.chat.id.0, &questions, pool).await?;
+            format!(
+                "Saved {} join questions, they are asked to users asking to join in verify mode.",
+                questions.len()
+            )
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Sends the answers to join questions to another chat, if the caller administers it.
+async fn use_join_log(
+    bot: &crate::types::TBot,
+    message: &Message,
+    caller: &teloxide::types::User,
+    log_chat: ChatId,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<String> {
+    // the answers are personal, so they only go where the caller is in charge
+    let caller_is_admin = bot
+        .get_chat_member(log_chat, caller.id)
+        .await
+        .is_ok_and(|member| member.is_privileged());
+    if !caller_is_admin {
+        return Ok("You need to be an admin of that chat to send the answers there!".to_owned());
+    }
+
+    // make sure the bot is able to post there before relying on it
+    let res = bot
+        .send_message(
+            log_chat,
+            format!(
+                "Answers to the join questions of {} will be sent here.",
+                html::escape(message.chat.title().unwrap_or("a chat"))
+            ),
+        )
+        .await;
+
+    Ok(match res {
+        Ok(_) => {
+            db::set_join_log_chat(message.chat.id.0, Some(log_chat.0), pool).await?;
+            "Answers to the join questions will now be sent to that chat.".to_owned()
+        }
+        Err(e) => format!(
+            "I can't send messages to that chat: {}",
+            html::escape(&e.to_string())
+        ),
+    })
+}
+
+/// Sets the chat the answers to join questions are sent to.
+pub async fn set_join_log(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let caller = message.from().ok_or(anyhow!("User not found"))?;
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("off" | "no") => {
+            db::set_join_log_chat(message.chat.id.0, None, pool).await?;
+            "Answers to the join questions will no longer be sent anywhere.".to_owned()
+        }
+        Some("here") => {
+            db::set_join_log_chat(message.chat.id.0, Some(message.chat.id.0), pool).await?;
+            "Answers to the join questions will now be sent to this chat.".to_owned()
+        }
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(log_chat) => use_join_log(bot, message, caller, ChatId(log_chat), pool).await?,
+            Err(_) => "You need to give me the ID of a chat, or off!".to_owned(),
+        },
+        None => {
+            "Usage: /joinlog <chat id>, /joinlog here, or /joinlog off to keep the answers private."
+                .to_owned()
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
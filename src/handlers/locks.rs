@@ -0,0 +1,408 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{MessageEntityKind, MessageKind},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, approvals, log_channel::LogEntry},
+    types::{LockKind, LogCategory},
+    utils::{db, perms},
+};
+
+/// Whether a character is a pictograph, which is shown as an emoji on its own.
+fn is_pictograph(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF) // pictographs, emoticons, flags and skin tones
+}
+
+/// Whether a character only modifies the emoji before it.
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(
+        c as u32,
+        0xFE0F | 0x200D | 0x20E3 // variation selector, joiner and keycap
+        | 0xE0020..=0xE007F // tags of subdivision flags
+    )
+}
+
+/// Whether a character is a symbol that is only shown as an emoji when asked to, eg. ❤️.
+fn is_emoji_symbol(c: char) -> bool {
+    c.is_ascii_digit()
+        || matches!(c as u32,
+            0x23 | 0x2A // keycaps
+            | 0xA9 | 0xAE | 0x2122 | 0x3030 | 0x303D | 0x3297 | 0x3299
+            | 0x2190..=0x21FF // arrows
+            | 0x2300..=0x23FF // technical symbols
+            | 0x2600..=0x27BF // miscellaneous symbols and dingbats
+            | 0x2B00..=0x2BFF // arrows and shapes
+        )
+}
+
+/// Whether a text consists of emojis only, symbols counting when they ask to be shown as one.
+fn is_emoji_only(text: &str) -> bool {
+    let chars: Vec<_> = text.chars().collect();
+    let mut has_emoji = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_whitespace() || is_emoji_modifier(c) {
+            continue;
+        }
+
+        let presented = chars.get(i + 1) == Some(&'\u{FE0F}');
+        if is_pictograph(c) || is_emoji_symbol(c) && presented {
+            has_emoji = true;
+        } else {
+            return false;
+        }
+    }
+
+    has_emoji
+}
+
+/// Whether a text contains right-to-left script, eg. Hebrew or Arabic.
+fn has_rtl(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(c as u32,
+            0x0590..=0x08FF // Hebrew, Arabic, Syriac, Thaana, NKo and others
+            | 0xFB1D..=0xFDFF // Hebrew and Arabic presentation forms
+            | 0xFE70..=0xFEFF
+        )
+    })
+}
+
+fn is_invite_link(text: &str) -> bool {
+    let text = text.to_lowercase();
+    [
+        "t.me/+",
+        "t.me/joinchat/",
+        "telegram.me/joinchat/",
+        "telegram.me/+",
+    ]
+    .iter()
+    .any(|pattern| text.contains(pattern))
+}
+
+/// The locks a message breaks, bot additions aside.
+fn broken_locks(message: &Message) -> Vec<LockKind> {
+    let mut broken = Vec::new();
+
+    let text = message.text().or(message.caption()).unwrap_or_default();
+    let entities = message
+        .entities()
+        .or(message.caption_entities())
+        .unwrap_or_default();
+
+    let media_lock = if message.sticker().is_some() {
+        Some(LockKind::Sticker)
+    } else if message.animation().is_some() {
+        Some(LockKind::Gif)
+    } else if message.photo().is_some() {
+        Some(LockKind::Photo)
+    } else if message.video().is_some() || message.video_note().is_some() {
+        Some(LockKind::Video)
+    } else if message.document().is_some() {
+        Some(LockKind::Document)
+    } else if message.voice().is_some() {
+        Some(LockKind::Voice)
+    } else if message.audio().is_some() {
+        Some(LockKind::Audio)
+    } else if message.game().is_some() {
+        Some(LockKind::Game)
+    } else if message.poll().is_some() {
+        Some(LockKind::Poll)
+    } else if message.contact().is_some() {
+        Some(LockKind::Contact)
+    } else if message.location().is_some() || message.venue().is_some() {
+        Some(LockKind::Location)
+    } else {
+        None
+    };
+    broken.extend(media_lock);
+
+    let urls: Vec<_> = entities
+        .iter()
+        .filter_map(|entity| match &entity.kind {
+            MessageEntityKind::Url => {
+                // entity offsets count UTF-16 code units
+                let url: Vec<_> = text
+                    .encode_utf16()
+                    .skip(entity.offset)
+                    .take(entity.length)
+                    .collect();
+                Some(String::from_utf16_lossy(&url))
+            }
+            MessageEntityKind::TextLink { url } => Some(url.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    if !urls.is_empty() {
+        broken.push(LockKind::Url);
+    }
+    if is_invite_link(text) || urls.iter().any(|url| is_invite_link(url)) {
+        broken.push(LockKind::InviteLink);
+    }
+    if message.forward().is_some() {
+        broken.push(LockKind::Forward);
+    }
+    if message.via_bot.is_some() {
+        broken.push(LockKind::Inline);
+    }
+    if message.text().is_some_and(|t| t.starts_with('/')) {
+        broken.push(LockKind::Command);
+    }
+    if is_emoji_only(text) {
+        broken.push(LockKind::Emoji);
+    }
+    if has_rtl(text) {
+        broken.push(LockKind::Rtl);
+    }
+
+    broken
+}
+
+/// Whether a message comes from someone locks don't apply to.
+async fn is_exempt(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    // posts of the linked channel are automatically forwarded into the chat
+    let automatic_forward = matches!(
+        &message.kind,
+        MessageKind::Common(common) if common.is_automatic_forward
+    );
+    if automatic_forward || perms::is_anonymous_admin(message) {
+        return Ok(true);
+    }
+
+    let Some(user) = message.from() else {
+        return Ok(true);
+    };
+
+    Ok(perms::is_user_admin(bot, message, user.id).await.is_ok()
+        || approvals::is_approved(message, pool).await?)
+}
+
+/// Deletes messages breaking the locks of a chat, returning whether the message was deleted.
+///
+/// Bots added by non-admins are kicked if bots are locked.
+pub async fn check_locks(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    if message.chat.is_private() {
+        return Ok(false);
+    }
+
+    let locks = db::get_locks(message.chat.id.0, pool).await?;
+    if locks.is_empty() {
+        return Ok(false);
+    }
+    let is_locked = |kind: LockKind| locks.iter().any(|l| l == kind.as_str());
+
+    let added_bots: Vec<_> = message
+        .new_chat_members()
+        .unwrap_or_default()
+        .iter()
+        .filter(|user| user.is_bot && user.id.0 as i64 != *crate::BOT_ID)
+        .collect();
+    let broken: Vec<_> = broken_locks(message)
+        .into_iter()
+        .filter(|kind| is_locked(*kind))
+        .collect();
+    let breaks_lock = !broken.is_empty();
+
+    if !(breaks_lock || !added_bots.is_empty() && is_locked(LockKind::Bots)) {
+        return Ok(false);
+    }
+
+    if is_exempt(bot, message, pool).await? {
+        return Ok(false);
+    }
+
+    for user in added_bots {
+        if let Err(e) = admin::kick_member(bot, message.chat.id, user.id).await {
+            log::warn!(
+                "Unable to kick bot {} from {}: {e}",
+                user.id,
+                message.chat.id
+            );
+            continue;
+        }
+
+        LogEntry::new(LogCategory::Automated, "BOT_KICKED")
+            .user(user)
+            .detail("Lock", LockKind::Bots.as_str())
+            .send(bot, message, pool)
+            .await;
+    }
+
+    if breaks_lock {
+        // the bot may have lost its rights to delete messages
+        if let Err(e) = bot.delete_message(message.chat.id, message.id).await {
+            log::warn!(
+                "Unable to delete locked message in {}: {e}",
+                message.chat.id
+            );
+            return Ok(false);
+        }
+
+        let locks = broken
+            .iter()
+            .map(|kind| kind.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut entry = LogEntry::new(LogCategory::Automated, "LOCKED").detail("Lock", locks);
+        if let Some(user) = message.from() {
+            entry = entry.user(user);
+        }
+        entry.send(bot, message, pool).await;
+
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// Parses the lock kinds given to `/lock` and `/unlock`, `all` standing for every kind.
+fn parse_lock_args(message: &Message) -> anyhow::Result<Vec<LockKind>> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| t.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    if args.is_empty() {
+        return Err(anyhow::anyhow!(
+            "You need to tell me what to lock, use /locks to see what can be locked."
+        ));
+    }
+
+    if args.iter().any(|arg| arg.eq_ignore_ascii_case("all")) {
+        return Ok(LockKind::ALL.to_vec());
+    }
+
+    args.iter().map(|arg| arg.parse()).collect()
+}
+
+async fn update_locks(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+    lock: bool,
+) -> anyhow::Result<()> {
+    let kinds = match parse_lock_args(message) {
+        Ok(kinds) => kinds,
+        Err(e) => {
+            bot.send_message(message.chat.id, html::escape(&e.to_string()))
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut locks = db::get_locks(message.chat.id.0, pool).await?;
+    locks.retain(|l| !kinds.iter().any(|k| k.as_str() == l));
+    if lock {
+        locks.extend(kinds.iter().map(|k| k.as_str().to_owned()));
+    }
+    locks.sort();
+    db::set_locks(message.chat.id.0, &locks, pool).await?;
+
+    let names = kinds
+        .iter()
+        .map(|k| k.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    // locks are enforced by deleting messages rather than through the chat permissions,
+    // which would hold back approved users as well
+    let text = if lock {
+        format!("Locked {names} for non-admins.")
+    } else {
+        format!("Unlocked {names}.")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn lock(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    update_locks(bot, message, pool, true).await
+}
+
+pub async fn unlock(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    update_locks(bot, message, pool, false).await
+}
+
+/// Lists what can be locked, and what is locked in the chat.
+pub async fn locks(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let locks = db::get_locks(message.chat.id.0, pool).await?;
+
+    let list = LockKind::ALL
+        .iter()
+        .map(|kind| {
+            let locked = locks.iter().any(|l| l == kind.as_str());
+            format!(
+                "- {}: {}",
+                html::code_inline(kind.as_str()),
+                if locked { "locked" } else { "unlocked" }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    bot.send_message(
+        message.chat.id,
+        format!("These are the locks of this chat:\n{list}"),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_emoji_only_texts() {
+        assert!(is_emoji_only("😂"));
+        assert!(is_emoji_only("👍🏽 ❤️ 👨‍👩‍👧"));
+        assert!(!is_emoji_only("nice 😂"));
+        assert!(!is_emoji_only(" "));
+        assert!(is_emoji_only("1️⃣ ❤️"));
+        assert!(!is_emoji_only("→ © ® ⌚"));
+        assert!(!is_emoji_only("2 + 2"));
+    }
+
+    #[test]
+    fn detects_rtl_texts() {
+        assert!(has_rtl("שלום"));
+        assert!(has_rtl("hello مرحبا"));
+        assert!(!has_rtl("hello there"));
+    }
+
+    #[test]
+    fn detects_invite_links() {
+        assert!(is_invite_link("join us at https://t.me/+AbCdEf"));
+        assert!(is_invite_link("T.ME/joinchat/AbCdEf"));
+        assert!(!is_invite_link("https://t.me/somechannel"));
+    }
+}
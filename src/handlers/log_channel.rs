@@ -0,0 +1,399 @@
+use anyhow::anyhow;
+use sqlx::{Pool, Postgres};
+use teloxide::{prelude::*, types::User, utils::html};
+
+use crate::{
+    types::LogCategory,
+    utils::{db, perms},
+};
+
+/// A record of an action, sent to the log channel of the chat it happened in.
+pub struct LogEntry {
+    category: LogCategory,
+    event: &'static str,
+    admin: Option<User>,
+    user: Option<User>,
+    details: Vec<(&'static str, String)>,
+}
+
+impl LogEntry {
+    /// Starts a record of an event, which becomes its hashtag, eg. `MUTE`.
+    pub fn new(category: LogCategory, event: &'static str) -> Self {
+        Self {
+            category,
+            event,
+            admin: None,
+            user: None,
+            details: Vec::new(),
+        }
+    }
+
+    /// Sets the admin who took the action, taken from the logged message otherwise.
+    pub fn admin(mut self, admin: &User) -> Self {
+        self.admin = Some(admin.clone());
+        self
+    }
+
+    /// Sets the user the action was taken on.
+    pub fn user(mut self, user: &User) -> Self {
+        self.user = Some(user.clone());
+        self
+    }
+
+    /// Adds a line to the record, the value is escaped.
+    pub fn detail(mut self, name: &'static str, value: impl AsRef<str>) -> Self {
+        self.details.push((name, html::escape(value.as_ref())));
+        self
+    }
+
+    fn render(&self, chat_title: &str, chat_id: ChatId, link: Option<&str>) -> String {
+        let mention = |user: &User| {
+            format!(
+                "{} ({})",
+                html::user_mention(user.id.0 as i64, &user.full_name()),
+                html::code_inline(&user.id.to_string())
+            )
+        };
+
+        let mut lines = vec![
+            format!(
+                "<b>{}</b> ({})",
+                html::escape(chat_title),
+                html::code_inline(&chat_id.to_string())
+            ),
+            format!("#{}", self.event),
+        ];
+        if let Some(admin) = &self.admin {
+            lines.push(format!("<b>Admin:</b> {}", mention(admin)));
+        }
+        if let Some(user) = &self.user {
+            lines.push(format!("<b>User:</b> {}", mention(user)));
+        }
+        lines.extend(
+            self.details
+                .iter()
+                .map(|(name, value)| format!("<b>{name}:</b> {value}")),
+        );
+        if let Some(link) = link {
+            lines.push(html::link(link, "Go to message"));
+        }
+
+        lines.join("\n")
+    }
+
+    /// Sends the record for an action taken in reply to a message, linking to it.
+    ///
+    /// Failing to log never fails the action itself, so errors are only logged.
+    pub async fn send(
+        mut self,
+        bot: &crate::types::TBot,
+        message: &Message,
+        pool: &Pool<Postgres>,
+    ) {
+        // anonymous admins are posted as the chat itself, so there's nobody to name
+        if self.admin.is_none()
+            && self.category != LogCategory::Automated
+            && !perms::is_anonymous_admin(message)
+        {
+            self.admin = message.from().cloned();
+        }
+
+        let link = message.url().map(|url| url.to_string());
+        self.send_to_chat(bot, message.chat.id, link.as_deref(), pool)
+            .await;
+    }
+
+    /// Sends the record for an action that isn't tied to a message of the chat.
+    pub async fn send_to_chat(
+        self,
+        bot: &crate::types::TBot,
+        chat_id: ChatId,
+        link: Option<&str>,
+        pool: &Pool<Postgres>,
+    ) {
+        if let Err(e) = self.try_send(bot, chat_id, link, pool).await {
+            log::warn!("Unable to log {} of {chat_id}: {e}", self.event);
+        }
+    }
+
+    async fn try_send(
+        &self,
+        bot: &crate::types::TBot,
+        chat_id: ChatId,
+        link: Option<&str>,
+        pool: &Pool<Postgres>,
+    ) -> anyhow::Result<()> {
+        let settings = db::get_log_settings(chat_id.0, pool).await?;
+        let Some(log_chat_id) = settings.log_chat_id else {
+            return Ok(());
+        };
+        if !settings
+            .log_categories
+            .iter()
+            .any(|c| c == self.category.as_str())
+        {
+            return Ok(());
+        }
+
+        let chat = db::get_chat(chat_id.0, pool).await?;
+        let title = chat.chat_name.unwrap_or("Unknown chat".to_owned());
+
+        bot.send_message(ChatId(log_chat_id), self.render(&title, chat_id, link))
+            .disable_web_page_preview(true)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Records a settings command, for commands that change the settings of the chat.
+pub async fn log_settings_change(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) {
+    let command = message.text().unwrap_or_default();
+    LogEntry::new(LogCategory::Settings, "SETTINGS")
+        .detail("Command", command)
+        .send(bot, message, pool)
+        .await;
+}
+
+/// Whether a message is a `/setlog` command, which may be addressed to the bot.
+fn is_set_log_command(message: &Message) -> bool {
+    message
+        .text()
+        .and_then(|t| t.split_whitespace().next())
+        .and_then(|command| command.split('@').next())
+        .is_some_and(|command| command.eq_ignore_ascii_case("/setlog"))
+}
+
+/// Sets the log channel to the one a `/setlog` was forwarded from.
+///
+/// The command is sent in the channel first, and then forwarded to the chat.
+pub async fn set_log(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let caller = message.from().ok_or(anyhow!("User not found"))?;
+
+    let forwarded = if message.forward_from_chat().is_some() {
+        Some(message)
+    } else {
+        message.reply_to_message()
+    };
+    let channel = forwarded
+        .filter(|m| is_set_log_command(m))
+        .and_then(|m| m.forward_from_chat())
+        .filter(|chat| chat.is_channel());
+
+    let Some(channel) = channel else {
+        bot.send_message(
+            message.chat.id,
+            "Send /setlog in the channel you want to log to, then forward it here!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let can_post = bot
+        .get_chat_member(channel.id, UserId(*crate::BOT_ID as u64))
+        .await
+        .is_ok_and(|member| member.can_post_messages());
+    if !can_post {
+        bot.send_message(
+            message.chat.id,
+            "I need to be an admin that can post messages in that channel!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    // the log holds the names of the users of the chat, so it only goes where the caller is in charge
+    let caller_is_admin = bot
+        .get_chat_member(channel.id, caller.id)
+        .await
+        .is_ok_and(|member| member.is_privileged());
+    if !caller_is_admin {
+        bot.send_message(
+            message.chat.id,
+            "You need to be an admin of that channel to log to it!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    db::set_log_chat(message.chat.id.0, Some(channel.id.0), pool).await?;
+
+    let title = html::escape(message.chat.title().unwrap_or("this chat"));
+    bot.send_message(
+        channel.id,
+        format!("This channel now logs the actions taken in <b>{title}</b>."),
+    )
+    .await?;
+
+    // the forwarded command is of no use to the members of the chat
+    if message.forward_from_chat().is_some() {
+        if let Err(e) = bot.delete_message(message.chat.id, message.id).await {
+            log::warn!("Unable to delete /setlog in {}: {e}", message.chat.id);
+        }
+    }
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Actions taken in this chat will now be logged to {}.",
+            html::escape(channel.title().unwrap_or("the channel"))
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unset_log(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let settings = db::get_log_settings(message.chat.id.0, pool).await?;
+
+    let Some(log_chat_id) = settings.log_chat_id else {
+        bot.send_message(message.chat.id, "This chat doesn't have a log channel!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    };
+
+    db::set_log_chat(message.chat.id.0, None, pool).await?;
+
+    // the bot may have been removed from the channel already
+    let title = html::escape(message.chat.title().unwrap_or("this chat"));
+    if let Err(e) = bot
+        .send_message(
+            ChatId(log_chat_id),
+            format!("This channel no longer logs the actions taken in <b>{title}</b>."),
+        )
+        .await
+    {
+        log::warn!("Unable to notify log channel {log_chat_id}: {e}");
+    }
+
+    bot.send_message(
+        message.chat.id,
+        "Actions taken in this chat are no longer logged.",
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+/// Parses the categories given to `/log` and `/nolog`, `all` standing for every category.
+fn parse_category_args(message: &Message) -> anyhow::Result<Vec<LogCategory>> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| t.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    if args.iter().any(|arg| arg.eq_ignore_ascii_case("all")) {
+        return Ok(LogCategory::ALL.to_vec());
+    }
+
+    args.iter().map(|arg| arg.parse()).collect()
+}
+
+async fn update_categories(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+    enable: bool,
+) -> anyhow::Result<()> {
+    let settings = db::get_log_settings(message.chat.id.0, pool).await?;
+
+    let categories = match parse_category_args(message) {
+        Ok(categories) if categories.is_empty() => {
+            let list = LogCategory::ALL
+                .iter()
+                .map(|category| {
+                    let logged = settings
+                        .log_categories
+                        .iter()
+                        .any(|c| c == category.as_str());
+                    format!(
+                        "- {}: {}",
+                        html::code_inline(category.as_str()),
+                        if logged { "logged" } else { "not logged" }
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let channel = if settings.log_chat_id.is_some() {
+                "This chat has a log channel."
+            } else {
+                "This chat doesn't have a log channel, use /setlog to set one."
+            };
+
+            bot.send_message(
+                message.chat.id,
+                format!("{channel}\nThese are the log categories:\n{list}"),
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+        Ok(categories) => categories,
+        Err(e) => {
+            bot.send_message(message.chat.id, html::escape(&e.to_string()))
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut logged = settings.log_categories;
+    logged.retain(|c| !categories.iter().any(|category| category.as_str() == c));
+    if enable {
+        logged.extend(categories.iter().map(|c| c.as_str().to_owned()));
+    }
+    logged.sort();
+    db::set_log_categories(message.chat.id.0, &logged, pool).await?;
+
+    let names = categories
+        .iter()
+        .map(|c| c.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let text = if enable {
+        format!("Now logging {names}.")
+    } else {
+        format!("No longer logging {names}.")
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Shows the log settings of the chat, or starts logging the given categories.
+pub async fn log(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    update_categories(bot, message, pool, true).await
+}
+
+pub async fn no_log(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    update_categories(bot, message, pool, false).await
+}
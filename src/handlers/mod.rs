@@ -1,2 +1,19 @@
 pub mod admin;
+pub mod anon_admin;
+pub mod antiflood;
+pub mod approvals;
+pub mod captcha;
+pub mod clean_service;
+pub mod disabling;
+pub mod feds;
 pub mod filter;
+pub mod gbans;
+pub mod greetings;
+pub mod join_request;
+pub mod locks;
+pub mod log_channel;
+pub mod pin;
+pub mod purge;
+pub mod report;
+pub mod rules;
+pub mod warn;
@@ -0,0 +1,107 @@
+use anyhow::anyhow;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use crate::utils::perms::{self, Right};
+
+pub async fn pin(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let Some(to_pin) = message.reply_to_message() else {
+        bot.send_message(message.chat.id, "Reply to a message to pin it!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    };
+
+    // pins are silent unless asked otherwise
+    let loud = match message.text().and_then(|t| t.split_whitespace().nth(1)) {
+        None | Some("silent" | "quiet") => false,
+        Some("loud" | "notify") => true,
+        Some(_) => {
+            bot.send_message(
+                message.chat.id,
+                "I only understand 'loud' or 'silent' pins!",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    bot.pin_chat_message(message.chat.id, to_pin.id)
+        .disable_notification(!loud)
+        .await?;
+
+    bot.send_message(message.chat.id, "Pinned!")
+        .reply_to_message_id(to_pin.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn unpin(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    // unpin the replied message, or the most recent pin otherwise
+    let request = bot.unpin_chat_message(message.chat.id);
+    match message.reply_to_message() {
+        Some(to_unpin) => request.message_id(to_unpin.id).await?,
+        None => request.await?,
+    };
+
+    bot.send_message(message.chat.id, "Unpinned!")
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn unpin_all(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let keyboard = InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback("Yes, unpin all", "unpinall:confirm"),
+        InlineKeyboardButton::callback("Cancel", "unpinall:cancel"),
+    ]]);
+
+    bot.send_message(
+        message.chat.id,
+        "Are you sure you want to unpin all messages in this chat?",
+    )
+    .reply_markup(keyboard)
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn unpin_all_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+) -> anyhow::Result<()> {
+    let message = query
+        .message
+        .as_ref()
+        .ok_or(anyhow!("Unable to access callback message"))?;
+
+    // only admins that could have run the command themselves may answer
+    if !perms::has_right(bot, message.chat.id, query.from.id, Right::CanPin).await? {
+        bot.answer_callback_query(&query.id)
+            .text("You need to be an admin with the right to pin messages to do this!")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let text = match data {
+        "confirm" => {
+            bot.unpin_all_chat_messages(message.chat.id).await?;
+            "Unpinned all messages!"
+        }
+        _ => "Unpinning all messages was cancelled.",
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use teloxide::{prelude::*, types::MessageId, RequestError};
+use tokio::task::JoinSet;
+
+/// Deletions aren't paced by the throttle adaptor, so they are sent this many at a time.
+const PURGE_CHUNK_SIZE: usize = 20;
+/// Pause between two chunks of deletions, to stay clear of the flood limits.
+const PURGE_CHUNK_DELAY: Duration = Duration::from_secs(1);
+/// How often a deletion is retried when Telegram asks to slow down.
+const MAX_DELETE_RETRIES: usize = 3;
+/// Upper bound for a purge, to keep a typo or an old reply from wiping a whole chat.
+const MAX_PURGE_COUNT: i32 = 1000;
+
+/// Deletes a message, waiting as long as asked to when rate limited.
+async fn delete_message(bot: &crate::types::TBot, chat_id: ChatId, message_id: MessageId) -> bool {
+    for _ in 0..=MAX_DELETE_RETRIES {
+        match bot.delete_message(chat_id, message_id).await {
+            Ok(_) => return true,
+            Err(RequestError::RetryAfter(wait)) => tokio::time::sleep(wait).await,
+            Err(e) => {
+                log::debug!(
+                    "Unable to delete message {} in {chat_id}: {e}",
+                    message_id.0
+                );
+                return false;
+            }
+        }
+    }
+
+    false
+}
+
+/// Deletes the given messages in small paced chunks, returning how many were actually deleted.
+pub async fn delete_messages(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    message_ids: Vec<MessageId>,
+) -> usize {
+    let mut deleted = 0;
+
+    for (i, chunk) in message_ids.chunks(PURGE_CHUNK_SIZE).enumerate() {
+        if i > 0 {
+            tokio::time::sleep(PURGE_CHUNK_DELAY).await;
+        }
+
+        let mut tasks = JoinSet::new();
+        for &message_id in chunk {
+            let bot = bot.clone();
+            tasks.spawn(async move { delete_message(&bot, chat_id, message_id).await });
+        }
+
+        while let Some(res) = tasks.join_next().await {
+            if matches!(res, Ok(true)) {
+                deleted += 1;
+            }
+        }
+    }
+
+    deleted
+}
+
+pub async fn purge(
+    bot: &crate::types::TBot,
+    message: &Message,
+    silent: bool,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    // purge from the replied message, or the given number of messages before the command
+    let count = match (message.reply_to_message(), arg) {
+        (Some(reply), _) => Some(message.id.0 - reply.id.0),
+        (None, Some(count)) => count.parse::<i32>().ok(),
+        (None, None) => {
+            bot.send_message(
+                message.chat.id,
+                "Reply to a message to purge from, or tell me how many messages to purge!",
+            )
+            .reply_to_message_id(message.id)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    // replying to an old message would otherwise wipe out the chat just as well
+    let Some(count) = count.filter(|count| (1..=MAX_PURGE_COUNT).contains(count)) else {
+        bot.send_message(
+            message.chat.id,
+            format!("Give me a number of messages between 1 and {MAX_PURGE_COUNT}!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+    let start = message.id.0 - count;
+
+    let started = Instant::now();
+    let message_ids: Vec<_> = (start.max(1)..=message.id.0).map(MessageId).collect();
+    let total = message_ids.len();
+    let deleted = delete_messages(bot, message.chat.id, message_ids).await;
+
+    log::info!("Purged {deleted} messages in chat {}", message.chat.id);
+
+    if !silent {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Purged {deleted} messages in {:.2}s.{}",
+                started.elapsed().as_secs_f32(),
+                if deleted < total {
+                    // gaps in the IDs, eg. messages deleted before, count as failures too
+                    format!(
+                        " {} messages couldn't be deleted, they may be too old or gone already.",
+                        total - deleted
+                    )
+                } else {
+                    String::new()
+                }
+            ),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn delete(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let Some(to_delete) = message.reply_to_message() else {
+        bot.send_message(message.chat.id, "Reply to a message to delete it!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    };
+
+    delete_messages(bot, message.chat.id, vec![to_delete.id, message.id]).await;
+
+    Ok(())
+}
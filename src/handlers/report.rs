@@ -0,0 +1,328 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, log_channel::LogEntry},
+    types::LogCategory,
+    utils::{
+        admin_cache, db,
+        perms::{self, Right},
+    },
+};
+
+/// How long a user has to wait between two reports in the same chat.
+const REPORT_COOLDOWN: Duration = Duration::from_secs(2 * 60);
+
+lazy_static! {
+    static ref LAST_REPORTS: Mutex<HashMap<(ChatId, UserId), Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Whether a message calls for admins with an `@admin` or `@admins` mention.
+pub fn is_admin_mention(text: &str) -> bool {
+    text.split_whitespace()
+        .any(|word| matches!(word.to_lowercase().as_str(), "@admin" | "@admins"))
+}
+
+/// Records a report, returning `false` if the user is still on cooldown.
+fn check_cooldown(chat_id: ChatId, user_id: UserId) -> bool {
+    let mut last_reports = LAST_REPORTS.lock().unwrap();
+    last_reports.retain(|_, reported_at| reported_at.elapsed() < REPORT_COOLDOWN);
+
+    if last_reports.contains_key(&(chat_id, user_id)) {
+        return false;
+    }
+
+    last_reports.insert((chat_id, user_id), Instant::now());
+    true
+}
+
+fn report_keyboard(
+    chat_id: ChatId,
+    user_id: UserId,
+    message_id: MessageId,
+) -> InlineKeyboardMarkup {
+    let data = |action: &str| {
+        format!(
+            "report:{action}:{}:{}:{}",
+            chat_id.0, user_id.0, message_id.0
+        )
+    };
+
+    InlineKeyboardMarkup::new([
+        vec![
+            InlineKeyboardButton::callback("Kick", data("kick")),
+            InlineKeyboardButton::callback("Ban", data("ban")),
+        ],
+        vec![
+            InlineKeyboardButton::callback("Delete message", data("del")),
+            InlineKeyboardButton::callback("Dismiss", data("dismiss")),
+        ],
+    ])
+}
+
+/// Reports the replied message to the admins of the chat.
+pub async fn report(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let reporter = message.from().ok_or(anyhow!("User not found"))?;
+
+    if !db::get_report_setting(message.chat.id.0, pool)
+        .await?
+        .unwrap_or(true)
+    {
+        return Ok(());
+    }
+
+    let Some(reported) = message.reply_to_message() else {
+        bot.send_message(
+            message.chat.id,
+            "Reply to a message to report it to the admins!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let user = reported
+        .from()
+        .ok_or(anyhow!("Unable to access reported user"))?;
+
+    // reporting admins or yourself does nothing useful
+    if user.id == reporter.id || perms::is_user_admin(bot, message, user.id).await.is_ok() {
+        return Ok(());
+    }
+
+    if !check_cooldown(message.chat.id, reporter.id) {
+        bot.send_message(
+            message.chat.id,
+            "You've reported a message recently, please give the admins some time!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let admins: Vec<_> = admin_cache::get_chat_admins(bot, message.chat.id)
+        .await?
+        .into_iter()
+        .filter(|admin| !admin.user.is_bot)
+        .collect();
+
+    // text after the command, or the whole message for @admin mentions
+    let reason = match message.text() {
+        Some(text) if text.starts_with('/') => text.split_once(char::is_whitespace).map(|(_, r)| r),
+        text => text,
+    };
+
+    let mention = html::user_mention(user.id.0 as i64, &user.full_name());
+    let keyboard = report_keyboard(message.chat.id, user.id, reported.id);
+
+    // mention the admins without cluttering the chat with their names
+    let hidden_mentions = admins
+        .iter()
+        .map(|admin| html::user_mention(admin.user.id.0 as i64, "\u{200b}"))
+        .collect::<String>();
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Reported {mention} to the admins.{}{hidden_mentions}",
+            admin::format_reason(reason)
+        ),
+    )
+    .reply_markup(keyboard.clone())
+    .reply_to_message_id(reported.id)
+    .await?;
+
+    // admins that opted in get the report by PM as well
+    let admin_ids: Vec<_> = admins.iter().map(|a| a.user.id.0 as i64).collect();
+    let pm_users = db::get_report_pm_users(&admin_ids, pool).await?;
+
+    let link = reported
+        .url()
+        .map(|url| html::link(url.as_str(), "reported message"))
+        .unwrap_or("reported message".to_owned());
+
+    for user_id in pm_users {
+        let res = bot
+            .send_message(
+                ChatId(user_id),
+                format!(
+                    "{} reported {mention} in {}, see the {link}.{}",
+                    html::user_mention(reporter.id.0 as i64, &reporter.full_name()),
+                    html::escape(message.chat.title().unwrap_or("a chat")),
+                    admin::format_reason(reason)
+                ),
+            )
+            .reply_markup(keyboard.clone())
+            .await;
+
+        // admins might have blocked the bot since opting in
+        if let Err(e) = res {
+            log::warn!("Unable to send report to {user_id}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Toggles reports in a group, or receiving reports by PM in private chats.
+pub async fn set_reports(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let private = message.chat.is_private();
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_report_setting(message.chat.id.0, true, pool).await?;
+            if private {
+                "You'll now receive reports from chats you're an admin in by PM."
+            } else {
+                "Users can now report messages to the admins of this chat."
+            }
+        }
+        Some("off" | "no") => {
+            db::set_report_setting(message.chat.id.0, false, pool).await?;
+            if private {
+                "You'll no longer receive reports by PM."
+            } else {
+                "Users can no longer report messages in this chat."
+            }
+        }
+        _ => {
+            // reports are on by default in groups, and off in PMs
+            let enabled = db::get_report_setting(message.chat.id.0, pool)
+                .await?
+                .unwrap_or(!private);
+            match (private, enabled) {
+                (true, true) => "You're receiving reports by PM. Use /reports off to stop.",
+                (true, false) => "You aren't receiving reports by PM. Use /reports on to start.",
+                (false, true) => "Reports are enabled in this chat.",
+                (false, false) => "Reports are disabled in this chat.",
+            }
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn report_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let message = query
+        .message
+        .as_ref()
+        .ok_or(anyhow!("Unable to access callback message"))?;
+
+    let args: Vec<_> = data.split(':').collect();
+    let [action, chat_id, user_id, message_id] = args[..] else {
+        return Err(anyhow!("Malformed report callback: {data}"));
+    };
+    let chat_id = ChatId(chat_id.parse()?);
+    let user_id = UserId(user_id.parse()?);
+    let message_id = MessageId(message_id.parse()?);
+
+    // the report may be handled from a PM, so rights are checked in the reported chat
+    let right = match action {
+        "del" => Some(Right::CanDelete),
+        "kick" | "ban" => Some(Right::CanRestrict),
+        _ => None,
+    };
+
+    let allowed = match right {
+        Some(right) => perms::has_right(bot, chat_id, query.from.id, right).await?,
+        None => admin_cache::get_admin(bot, chat_id, query.from.id)
+            .await?
+            .is_some(),
+    };
+
+    if !allowed {
+        bot.answer_callback_query(&query.id)
+            .text("You don't have the rights to do this!")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let res = match action {
+        "kick" => admin::kick_member(bot, chat_id, user_id).await,
+        "ban" => bot.ban_chat_member(chat_id, user_id).await.map(|_| ()),
+        "del" => bot.delete_message(chat_id, message_id).await.map(|_| ()),
+        _ => Ok(()),
+    };
+
+    let outcome = match (action, res) {
+        (_, Err(e)) => {
+            bot.answer_callback_query(&query.id)
+                .text(format!("That didn't work: {e}"))
+                .show_alert(true)
+                .await?;
+            return Ok(());
+        }
+        ("kick", _) => "kicked the user",
+        ("ban", _) => "banned the user",
+        ("del", _) => "deleted the message",
+        _ => "dismissed the report",
+    };
+
+    let event = match action {
+        "kick" => Some("KICK"),
+        "ban" => Some("BAN"),
+        "del" => Some("DELETE"),
+        _ => None,
+    };
+    if let Some(event) = event {
+        // the report may be handled from a PM, so the record links to the reported message
+        let link = Message::url_of(chat_id, None, message_id).map(|url| url.to_string());
+        LogEntry::new(LogCategory::Admin, event)
+            .admin(&query.from)
+            .detail("User ID", user_id.to_string())
+            .detail("Via", "report")
+            .send_to_chat(bot, chat_id, link.as_deref(), pool)
+            .await;
+    }
+
+    // the text of the report has lost its mentions, so it is written anew
+    let name = db::get_user(Some(user_id.0 as i64), None, pool)
+        .await
+        .map(|u| u.full_name)
+        .unwrap_or_else(|_| user_id.to_string());
+
+    bot.answer_callback_query(&query.id).await?;
+    bot.edit_message_text(
+        message.chat.id,
+        message.id,
+        format!(
+            "Reported {} to the admins.\n\n{} {outcome}.",
+            html::user_mention(user_id.0 as i64, &name),
+            html::user_mention(query.from.id.0 as i64, &query.from.full_name())
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::utils::{db, formatting};
+
+/// Prefix of the `/start` payload that delivers the rules of a chat by PM.
+const RULES_PAYLOAD: &str = "rules_";
+
+/// A link that opens a PM with the bot and has it send the rules of a chat.
+pub async fn rules_link(bot: &crate::types::TBot, chat_id: ChatId) -> anyhow::Result<String> {
+    let me = bot.get_me().await?;
+    Ok(format!(
+        "https://t.me/{}?start={RULES_PAYLOAD}{}",
+        me.username(),
+        chat_id.0
+    ))
+}
+
+/// Replaces the `{rules}` filling with a button linking to the rules of the chat.
+pub async fn fill_rules_button(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    text: &str,
+) -> anyhow::Result<String> {
+    if !text.contains("{rules}") {
+        return Ok(text.to_owned());
+    }
+
+    let link = rules_link(bot, chat_id).await?;
+    Ok(text.replace("{rules}", &format!("\n[Rules](buttonurl://{link})")))
+}
+
+/// Returns the chat whose rules a `/start` payload asks for, if it does.
+pub fn rules_payload(payload: &str) -> Option<ChatId> {
+    payload
+        .strip_prefix(RULES_PAYLOAD)
+        .and_then(|id| id.parse().ok())
+        .map(ChatId)
+}
+
+pub async fn rules(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let rules = db::get_rules(message.chat.id.0, pool).await?;
+
+    let Some(text) = rules.rules_text else {
+        bot.send_message(message.chat.id, "This chat doesn't have any rules yet.")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Ok(());
+    };
+
+    if rules.private_rules {
+        let keyboard = InlineKeyboardMarkup::new([[InlineKeyboardButton::url(
+            "Rules",
+            rules_link(bot, message.chat.id).await?.parse()?,
+        )]]);
+
+        bot.send_message(
+            message.chat.id,
+            "Press the button below to read the rules of this chat.",
+        )
+        .reply_markup(keyboard)
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    // answer the message the rules were asked for in reply to, if any
+    let reply_to = message.reply_to_message().unwrap_or(message).id;
+    formatting::send_content(bot, message.chat.id, &text, None, Some(reply_to)).await?;
+
+    Ok(())
+}
+
+/// Sends the rules of a chat by PM, for users following a rules link.
+pub async fn send_rules_pm(
+    bot: &crate::types::TBot,
+    message: &Message,
+    chat_id: ChatId,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    // the link may have been made up, or point to a chat the bot has left
+    let Some(chat) = db::find_chat(chat_id.0, pool).await? else {
+        bot.send_message(message.chat.id, "I don't know that chat!")
+            .await?;
+        return Ok(());
+    };
+    let rules = db::get_rules(chat_id.0, pool).await?;
+    let title = html::escape(&chat.chat_name.unwrap_or("the chat".to_owned()));
+
+    let Some(text) = rules.rules_text else {
+        bot.send_message(
+            message.chat.id,
+            format!("{title} doesn't have any rules yet."),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    bot.send_message(message.chat.id, format!("The rules of <b>{title}</b> are:"))
+        .await?;
+    formatting::send_content(bot, message.chat.id, &text, None, None).await?;
+
+    Ok(())
+}
+
+/// Sets the rules of a chat from the command text, or from the replied message,
+/// once a preview of them went through.
+pub async fn set_rules(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args = message
+        .text()
+        .and_then(|t| t.split_once(char::is_whitespace))
+        .map(|(_, args)| args.trim())
+        .filter(|args| !args.is_empty());
+    let text = args.or_else(|| {
+        message
+            .reply_to_message()
+            .and_then(|r| r.text().or(r.caption()))
+    });
+
+    let Some(text) = text else {
+        bot.send_message(
+            message.chat.id,
+            "You need to give me the rules, or reply to a message to use it as the rules!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    // the rules are previewed to check that Telegram accepts their formatting
+    let preview =
+        formatting::send_content(bot, message.chat.id, text, None, Some(message.id)).await;
+
+    if let Err(e) = preview {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "I couldn't send those rules, so they weren't saved: {}",
+                html::escape(&e.to_string())
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    db::set_rules(message.chat.id.0, Some(text), pool).await?;
+
+    bot.send_message(message.chat.id, "Saved the rules of this chat.")
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn clear_rules(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    db::set_rules(message.chat.id.0, None, pool).await?;
+
+    bot.send_message(message.chat.id, "Cleared the rules of this chat.")
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+/// Toggles sending the rules by PM instead of in the chat.
+pub async fn private_rules(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        Some("on" | "yes") => {
+            db::set_private_rules(message.chat.id.0, true, pool).await?;
+            "/rules will now send the rules by PM."
+        }
+        Some("off" | "no") => {
+            db::set_private_rules(message.chat.id.0, false, pool).await?;
+            "/rules will now send the rules in this chat."
+        }
+        _ => {
+            let rules = db::get_rules(message.chat.id.0, pool).await?;
+            if rules.private_rules {
+                "The rules are sent by PM. Use /privaterules off to send them in the chat."
+            } else {
+                "The rules are sent in the chat. Use /privaterules on to send them by PM."
+            }
+        }
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,532 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{Pool, Postgres};
+use teloxide::{
+    prelude::*,
+    types::{ChatMember, InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::html,
+};
+
+use crate::{
+    handlers::{admin, log_channel::LogEntry},
+    types::{
+        db::{Warn, WarnSettings},
+        LogCategory, Punishment,
+    },
+    utils::{
+        db,
+        perms::{self, Right},
+        time,
+    },
+};
+
+/// Warn limits outside of this range make little sense for a chat.
+const WARN_LIMIT_RANGE: std::ops::RangeInclusive<i32> = 1..=100;
+
+/// The time warnings issued before have expired at, if they expire at all.
+fn warns_since(settings: &WarnSettings) -> Option<DateTime<Utc>> {
+    settings
+        .warn_time
+        .map(|secs| Utc::now() - Duration::seconds(secs))
+}
+
+/// Fetches the warnings of a user that still count toward the warn limit.
+async fn get_active_warns(
+    chat_id: i64,
+    user_id: i64,
+    settings: &WarnSettings,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<Warn>> {
+    db::get_warns(chat_id, user_id, warns_since(settings), pool).await
+}
+
+/// Adds a warning to the target, applying the chat's warn mode once they hit the limit.
+async fn warn_member(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+    member: &ChatMember,
+    reason: Option<String>,
+    announce: bool,
+) -> anyhow::Result<()> {
+    let issuer = message.from().ok_or(anyhow!("User not found"))?;
+    let chat_id = message.chat.id.0;
+    let user_id = member.user.id.0 as i64;
+
+    let warn = db::insert_warn(
+        chat_id,
+        user_id,
+        reason.as_deref(),
+        issuer.id.0 as i64,
+        pool,
+    )
+    .await?;
+
+    let settings = db::get_warn_settings(chat_id, pool).await?;
+    let warns = get_active_warns(chat_id, user_id, &settings, pool).await?;
+
+    let mention = html::user_mention(user_id, &member.user.full_name());
+
+    let mut entry = LogEntry::new(LogCategory::Warns, "WARN")
+        .user(&member.user)
+        .detail("Warns", format!("{}/{}", warns.len(), settings.warn_limit));
+    if let Some(reason) = &reason {
+        entry = entry.detail("Reason", reason);
+    }
+    entry.send(bot, message, pool).await;
+
+    if (warns.len() as i32) < settings.warn_limit {
+        if !announce {
+            return Ok(());
+        }
+
+        let keyboard = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+            "Remove warn",
+            format!("rmwarn:{}", warn.warn_id),
+        )]]);
+
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "{mention} has {}/{} warnings.{}",
+                warns.len(),
+                settings.warn_limit,
+                admin::format_reason(reason.as_deref())
+            ),
+        )
+        .reply_markup(keyboard)
+        .await?;
+
+        return Ok(());
+    }
+
+    // the limit has been hit, punish the user and start over
+    let punishment: Punishment = settings.warn_mode.parse()?;
+    let duration = settings.warn_mode_duration.map(Duration::seconds);
+
+    if let Err(e) = admin::punish(bot, message.chat.id, member.user.id, punishment, duration).await
+    {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "{mention} has hit the warn limit, but I couldn't {} them, so their warnings are kept: {}",
+                punishment.as_str(),
+                html::escape(&e.to_string())
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // only start over once the user has actually been dealt with
+    db::reset_warns(chat_id, user_id, pool).await?;
+
+    LogEntry::new(LogCategory::Warns, "WARN_LIMIT")
+        .user(&member.user)
+        .detail("Action", admin::describe_punishment(punishment, duration))
+        .send(bot, message, pool)
+        .await;
+
+    let reasons = warns
+        .iter()
+        .filter_map(|w| w.reason.as_deref())
+        .map(|r| format!("- {}", html::escape(r)))
+        .fold(String::new(), |acc, ref v| acc + v + "\n");
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "That's {}/{} warnings; {mention} has been {}!\n{reasons}",
+            warns.len(),
+            settings.warn_limit,
+            admin::describe_punishment(punishment, duration)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn warn(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, reason)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !admin::can_restrict_target(bot, message, &member, "warn").await? {
+        return Ok(());
+    }
+
+    warn_member(bot, message, pool, &member, reason, true).await
+}
+
+/// Warns the author of the replied message and deletes it.
+pub async fn delete_and_warn(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some(replied) = message.reply_to_message() else {
+        bot.send_message(
+            message.chat.id,
+            "Reply to a message to delete it and warn its sender!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let Some((member, reason)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !admin::can_restrict_target(bot, message, &member, "warn").await? {
+        return Ok(());
+    }
+
+    bot.delete_message(message.chat.id, replied.id).await?;
+
+    warn_member(bot, message, pool, &member, reason, true).await
+}
+
+/// Warns the target without announcing it, deleting the command message.
+pub async fn silent_warn(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, reason)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    if !admin::can_restrict_target(bot, message, &member, "warn").await? {
+        return Ok(());
+    }
+
+    bot.delete_message(message.chat.id, message.id).await?;
+
+    warn_member(bot, message, pool, &member, reason, false).await
+}
+
+pub async fn warns(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let has_target = message.reply_to_message().is_some()
+        || message
+            .text()
+            .is_some_and(|t| t.split_whitespace().count() > 1);
+
+    // without a target, users get to see their own warnings
+    let user = if has_target {
+        match admin::extract_target(bot, message, pool).await? {
+            Some((member, _)) => member.user,
+            None => return Ok(()),
+        }
+    } else {
+        message.from().ok_or(anyhow!("User not found"))?.clone()
+    };
+
+    let chat_id = message.chat.id.0;
+    let settings = db::get_warn_settings(chat_id, pool).await?;
+    let warns = get_active_warns(chat_id, user.id.0 as i64, &settings, pool).await?;
+
+    let mention = html::user_mention(user.id.0 as i64, &user.full_name());
+
+    if warns.is_empty() {
+        bot.send_message(
+            message.chat.id,
+            format!("{mention} doesn't have any warnings!"),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    }
+
+    let fmt_warns = warns
+        .iter()
+        .map(|w| {
+            format!(
+                "- {}: {}",
+                w.created_at.format("%Y-%m-%d"),
+                html::escape(w.reason.as_deref().unwrap_or("No reason given"))
+            )
+        })
+        .fold(String::new(), |acc, ref v| acc + v + "\n");
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "{mention} has {}/{} warnings:\n{fmt_warns}{}",
+            warns.len(),
+            settings.warn_limit,
+            settings
+                .warn_time
+                .map(|secs| format!(
+                    "\nWarnings expire after {}.",
+                    time::format_duration(Duration::seconds(secs))
+                ))
+                .unwrap_or_default()
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_warn(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, _)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    let mention = html::user_mention(member.user.id.0 as i64, &member.user.full_name());
+    let settings = db::get_warn_settings(message.chat.id.0, pool).await?;
+
+    let removed = db::delete_latest_warn(
+        message.chat.id.0,
+        member.user.id.0 as i64,
+        warns_since(&settings),
+        pool,
+    )
+    .await?;
+    let text = match removed {
+        Some(_) => {
+            LogEntry::new(LogCategory::Warns, "RMWARN")
+                .user(&member.user)
+                .send(bot, message, pool)
+                .await;
+            format!("Removed the latest warning of {mention}.")
+        }
+        None => format!("{mention} doesn't have any active warnings!"),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn reset_warns(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let Some((member, _)) = admin::extract_target(bot, message, pool).await? else {
+        return Ok(());
+    };
+
+    db::reset_warns(message.chat.id.0, member.user.id.0 as i64, pool).await?;
+
+    LogEntry::new(LogCategory::Warns, "RESETWARNS")
+        .user(&member.user)
+        .send(bot, message, pool)
+        .await;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Reset the warnings of {}.",
+            html::user_mention(member.user.id.0 as i64, &member.user.full_name())
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn set_warn_limit(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let Some(arg) = arg else {
+        let settings = db::get_warn_settings(message.chat.id.0, pool).await?;
+        bot.send_message(
+            message.chat.id,
+            format!("The current warn limit is {}.", settings.warn_limit),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let text = match arg.parse::<i32>() {
+        Ok(limit) if WARN_LIMIT_RANGE.contains(&limit) => {
+            db::set_warn_limit(message.chat.id.0, limit, pool).await?;
+            format!("Updated the warn limit to {limit}.")
+        }
+        _ => format!(
+            "The warn limit has to be a number between {} and {}!",
+            WARN_LIMIT_RANGE.start(),
+            WARN_LIMIT_RANGE.end()
+        ),
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_warn_mode(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let args: Vec<_> = message
+        .text()
+        .map(|t| t.split_whitespace().skip(1).collect())
+        .unwrap_or_default();
+
+    let Some(mode) = args.first() else {
+        let settings = db::get_warn_settings(message.chat.id.0, pool).await?;
+        let punishment: Punishment = settings.warn_mode.parse()?;
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "Users hitting the warn limit are currently {}.",
+                admin::describe_punishment(
+                    punishment,
+                    settings.warn_mode_duration.map(Duration::seconds)
+                )
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Ok(());
+    };
+
+    let (punishment, duration) = match admin::parse_punishment(mode, args.get(1).copied()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            bot.send_message(message.chat.id, e.to_string())
+                .reply_to_message_id(message.id)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    db::set_warn_mode(
+        message.chat.id.0,
+        punishment.as_str(),
+        duration.map(|d| d.num_seconds()),
+        pool,
+    )
+    .await?;
+
+    bot.send_message(
+        message.chat.id,
+        format!(
+            "Users hitting the warn limit will now be {}.",
+            admin::describe_punishment(punishment, duration)
+        ),
+    )
+    .reply_to_message_id(message.id)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_warn_callback(
+    bot: &crate::types::TBot,
+    query: &CallbackQuery,
+    data: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let message = query
+        .message
+        .as_ref()
+        .ok_or(anyhow!("Unable to access callback message"))?;
+
+    if !perms::has_right(bot, message.chat.id, query.from.id, Right::CanRestrict).await? {
+        bot.answer_callback_query(&query.id)
+            .text("You need to be an admin with the right to restrict members to do this!")
+            .show_alert(true)
+            .await?;
+        return Ok(());
+    }
+
+    let settings = db::get_warn_settings(message.chat.id.0, pool).await?;
+    let removed = db::delete_warn(
+        message.chat.id.0,
+        data.parse()?,
+        warns_since(&settings),
+        pool,
+    )
+    .await?;
+    let text = match removed {
+        Some(warn) => {
+            LogEntry::new(LogCategory::Warns, "RMWARN")
+                .admin(&query.from)
+                .detail("User ID", warn.user_id.to_string())
+                .send(bot, message, pool)
+                .await;
+            format!(
+                "Warning removed by {}.",
+                html::user_mention(query.from.id.0 as i64, &query.from.full_name())
+            )
+        }
+        None => "This warning has already been removed, or has expired.".to_owned(),
+    };
+
+    bot.answer_callback_query(&query.id).await?;
+    bot.edit_message_text(message.chat.id, message.id, text)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_warn_time(
+    bot: &crate::types::TBot,
+    message: &Message,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    let arg = message.text().and_then(|t| t.split_whitespace().nth(1));
+
+    let text = match arg {
+        None => {
+            let settings = db::get_warn_settings(message.chat.id.0, pool).await?;
+            match settings.warn_time {
+                Some(secs) => format!(
+                    "Warnings currently expire after {}.",
+                    time::format_duration(Duration::seconds(secs))
+                ),
+                None => "Warnings currently never expire.".to_owned(),
+            }
+        }
+        Some("off" | "never") => {
+            db::set_warn_time(message.chat.id.0, None, pool).await?;
+            "Warnings will no longer expire.".to_owned()
+        }
+        Some(arg) => match time::parse_duration(arg) {
+            Ok(duration) => {
+                db::set_warn_time(message.chat.id.0, Some(duration.num_seconds()), pool).await?;
+                format!(
+                    "Warnings will now expire after {}.",
+                    time::format_duration(duration)
+                )
+            }
+            Err(e) => e.to_string(),
+        },
+    };
+
+    bot.send_message(message.chat.id, text)
+        .reply_to_message_id(message.id)
+        .await?;
+
+    Ok(())
+}
@@ -8,12 +8,22 @@ use dotenv::dotenv;
 use lazy_static::lazy_static;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use teloxide::{
-    adaptors::throttle::Limits, prelude::*, types::ParseMode, utils::command::BotCommands,
+    adaptors::throttle::Limits,
+    prelude::*,
+    types::{AllowedUpdate, ParseMode},
+    update_listeners::Polling,
+    utils::command::BotCommands,
 };
 
 use types::{commands::*, ConfigParameters, TBot};
 
-use crate::{handlers::filter, utils::db::save_details};
+use crate::{
+    handlers::{
+        admin, anon_admin, antiflood, approvals, captcha, clean_service, disabling, feds, filter,
+        gbans, greetings, join_request, locks, log_channel, pin, purge, report, rules, warn,
+    },
+    utils::{admin_cache, db::save_details, perms},
+};
 
 lazy_static! {
     static ref DATABASE_URL: String = std::env::var("DATABASE_URL").expect("Expected database url");
@@ -45,7 +55,7 @@ async fn main() {
         sudo: vec![UserId(850322305)],
     };
 
-    let handler = Update::filter_message()
+    let message_handler = Update::filter_message()
         .branch(
             dptree::entry()
                 .filter_command::<UserCommands>()
@@ -64,24 +74,97 @@ async fn main() {
             dptree::filter(|| true).endpoint(|bot: TBot, msg: Message| async move {
                 save_details(&bot, &msg).await?;
 
+                if locks::check_locks(&bot, &msg, &POOL).await? {
+                    return Ok(());
+                }
+
+                // messages that are part of a flood get no further handling
+                if antiflood::check_flood(&bot, &msg, &POOL).await? {
+                    return Ok(());
+                }
+
+                if let Some(members) = msg.new_chat_members() {
+                    // newcomers banned globally or in the federation of the chat aren't welcomed
+                    let removed = gbans::check_new_members(&bot, &msg, members, &POOL).await?;
+                    let members: Vec<_> = members
+                        .iter()
+                        .filter(|user| !removed.contains(&user.id))
+                        .cloned()
+                        .collect();
+                    let removed = feds::check_new_members(&bot, &msg, &members, &POOL).await?;
+                    let members: Vec<_> = members
+                        .into_iter()
+                        .filter(|user| !removed.contains(&user.id))
+                        .collect();
+
+                    if !members.is_empty() {
+                        captcha::challenge_members(&bot, &msg, &members, &POOL).await?;
+                        greetings::welcome_members(&bot, &msg, &members, &POOL).await?;
+                    }
+                }
+
+                if msg.left_chat_member().is_some() {
+                    greetings::goodbye_member(&bot, &msg, &POOL).await?;
+                }
+
+                clean_service::clean_service(&bot, &msg, &POOL).await?;
+
+                // answers to join questions are sent by PM
+                if msg.chat.is_private() && join_request::record_answer(&bot, &msg).await? {
+                    return Ok(());
+                }
+
                 // check if update contains any text
                 let text = msg.text();
                 if text.is_none() {
                     return Ok(());
                 }
 
-                // handle note
+                // handle @admin reports
                 let unwrapped_text = text.unwrap();
+                if !msg.chat.is_private()
+                    && msg.reply_to_message().is_some()
+                    && report::is_admin_mention(unwrapped_text)
+                    && !disabling::is_disabled(&bot, &msg, "report", &POOL).await?
+                {
+                    report::report(&bot, &msg, &POOL).await?;
+                }
+
+                // handle note
                 if unwrapped_text.starts_with('#')
                     && unwrapped_text.split_whitespace().count() < 2
                     && unwrapped_text != "#"
+                    && !disabling::is_disabled(&bot, &msg, "hashtag", &POOL).await?
                 {
-                    filter::get_note(&bot, &msg, false, &*POOL).await?;
+                    filter::get_note(&bot, &msg, false, &POOL).await?;
                 }
                 Ok(())
             }),
         );
 
+    let handler = dptree::entry()
+        .branch(message_handler)
+        .branch(Update::filter_callback_query().endpoint(callback_handler))
+        .branch(Update::filter_chat_member().endpoint(admin_cache::chat_member_handler))
+        .branch(Update::filter_my_chat_member().endpoint(admin_cache::chat_member_handler))
+        .branch(Update::filter_chat_join_request().endpoint(join_request::join_request_handler));
+
+    // chat member updates are only sent when explicitly asked for
+    let listener = Polling::builder(bot.clone())
+        .allowed_updates(vec![
+            AllowedUpdate::Message,
+            AllowedUpdate::CallbackQuery,
+            AllowedUpdate::MyChatMember,
+            AllowedUpdate::ChatMember,
+            AllowedUpdate::ChatJoinRequest,
+        ])
+        .build();
+
+    // captchas that expired while the bot was down are dealt with right away
+    if let Err(e) = captcha::resume_pending(&bot).await {
+        log::warn!("Unable to resume pending captchas: {e}");
+    }
+
     Dispatcher::builder(bot, handler)
         .dependencies(dptree::deps![params])
         .default_handler(|upd| async move {
@@ -92,7 +175,10 @@ async fn main() {
         ))
         .enable_ctrlc_handler()
         .build()
-        .dispatch()
+        .dispatch_with_listener(
+            listener,
+            LoggingErrorHandler::with_custom_text("An error from the update listener"),
+        )
         .await;
 
     log::info!("Exiting the bot");
@@ -100,44 +186,347 @@ async fn main() {
 
 async fn user_cmd_handler(bot: TBot, message: Message, cmd: UserCommands) -> anyhow::Result<()> {
     save_details(&bot, &message).await?;
+
+    // locked commands are deleted before they get to run
+    if locks::check_locks(&bot, &message, &POOL).await? {
+        return Ok(());
+    }
+
+    // disabled commands are dropped before their guards could answer the sender
+    if let Some(name) = cmd.disable_name() {
+        if disabling::is_disabled(&bot, &message, name, &POOL).await? {
+            return Ok(());
+        }
+    }
+
+    // anonymous admins have to prove who they are before their rights can be checked
+    if perms::is_anonymous_admin(&message) && cmd.identifies_sender() {
+        return anon_admin::request_verification(&bot, &message, cmd).await;
+    }
+
+    perms::require_guards(&bot, &message, cmd.guards()).await?;
+
     match cmd {
         UserCommands::Help => {
             bot.send_message(message.chat.id, UserCommands::descriptions().to_string())
                 .await?;
         }
         UserCommands::Start => {
-            bot.send_message(message.chat.id, "start_message").await?;
+            // deep links carry a payload, eg. to have the rules of a chat sent by PM
+            let payload = message.text().and_then(|t| t.split_whitespace().nth(1));
+            if let Some(chat_id) = payload.and_then(rules::rules_payload) {
+                rules::send_rules_pm(&bot, &message, chat_id, &POOL).await?;
+            } else if let Some(chat_id) = payload.and_then(join_request::answers_payload) {
+                join_request::start_answers(&bot, &message, chat_id).await?;
+            } else {
+                bot.send_message(message.chat.id, "start_message").await?;
+            }
         }
         UserCommands::Save => {
-            filter::save_note(&bot, &message, &*POOL).await?;
+            filter::save_note(&bot, &message, &POOL).await?;
         }
         UserCommands::Get => {
-            filter::get_note(&bot, &message, true, &*POOL).await?;
+            filter::get_note(&bot, &message, true, &POOL).await?;
         }
         UserCommands::Delete => {
-            filter::delete_note(&bot, &message, &*POOL).await?;
+            filter::delete_note(&bot, &message, &POOL).await?;
             // bot.send_message(message.chat.id, "deleted note!")
             //     .reply_to_message_id(message.id)
             //     .await?;
         }
         UserCommands::Notes => {
-            filter::get_all_notes(&bot, &message, &*POOL).await?;
+            filter::get_all_notes(&bot, &message, &POOL).await?;
             // bot.send_message(message.chat.id, "Following are all the notes in this chat!")
             //     .reply_to_message_id(message.id)
             //     .await?;
         }
+        UserCommands::Mute => {
+            admin::mute(&bot, &message, &POOL).await?;
+        }
+        UserCommands::TMute => {
+            admin::temp_mute(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Unmute => {
+            admin::unmute(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Kick => {
+            admin::kick(&bot, &message, &POOL).await?;
+        }
+        UserCommands::KickMe => {
+            admin::kick_me(&bot, &message).await?;
+        }
+        UserCommands::Promote => {
+            admin::promote(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FullPromote => {
+            admin::full_promote(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Demote => {
+            admin::demote(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Title => {
+            admin::set_title(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Pin => {
+            pin::pin(&bot, &message).await?;
+        }
+        UserCommands::Unpin => {
+            pin::unpin(&bot, &message).await?;
+        }
+        UserCommands::UnpinAll => {
+            pin::unpin_all(&bot, &message).await?;
+        }
+        UserCommands::Purge => {
+            purge::purge(&bot, &message, false).await?;
+        }
+        UserCommands::SPurge => {
+            purge::purge(&bot, &message, true).await?;
+        }
+        UserCommands::Del => {
+            purge::delete(&bot, &message).await?;
+        }
+        UserCommands::AdminCache => {
+            admin::refresh_admin_cache(&bot, &message).await?;
+        }
+        UserCommands::Warn => {
+            warn::warn(&bot, &message, &POOL).await?;
+        }
+        UserCommands::DWarn => {
+            warn::delete_and_warn(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SWarn => {
+            warn::silent_warn(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Warns => {
+            warn::warns(&bot, &message, &POOL).await?;
+        }
+        UserCommands::RmWarn => {
+            warn::remove_warn(&bot, &message, &POOL).await?;
+        }
+        UserCommands::ResetWarns => {
+            warn::reset_warns(&bot, &message, &POOL).await?;
+        }
+        UserCommands::WarnLimit => {
+            warn::set_warn_limit(&bot, &message, &POOL).await?;
+        }
+        UserCommands::WarnMode => {
+            warn::set_warn_mode(&bot, &message, &POOL).await?;
+        }
+        UserCommands::WarnTime => {
+            warn::set_warn_time(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Report => {
+            report::report(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Reports => {
+            report::set_reports(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Flood => {
+            antiflood::flood(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SetFlood => {
+            antiflood::set_flood(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SetFloodTimer => {
+            antiflood::set_flood_timer(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FloodMode => {
+            antiflood::set_flood_mode(&bot, &message, &POOL).await?;
+        }
+        UserCommands::ClearFlood => {
+            antiflood::set_clear_flood(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Welcome => {
+            greetings::welcome(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SetWelcome => {
+            greetings::set_welcome(&bot, &message, &POOL).await?;
+        }
+        UserCommands::ResetWelcome => {
+            greetings::reset_welcome(&bot, &message, &POOL).await?;
+        }
+        UserCommands::CleanWelcome => {
+            greetings::clean_welcome(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Goodbye => {
+            greetings::goodbye(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SetGoodbye => {
+            greetings::set_goodbye(&bot, &message, &POOL).await?;
+        }
+        UserCommands::ResetGoodbye => {
+            greetings::reset_goodbye(&bot, &message, &POOL).await?;
+        }
+        UserCommands::GoodbyeKicked => {
+            greetings::goodbye_kicked(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Captcha => {
+            captcha::captcha(&bot, &message, &POOL).await?;
+        }
+        UserCommands::CaptchaMode => {
+            captcha::set_captcha_mode(&bot, &message, &POOL).await?;
+        }
+        UserCommands::CaptchaTime => {
+            captcha::set_captcha_time(&bot, &message, &POOL).await?;
+        }
+        UserCommands::JoinRequests => {
+            join_request::join_requests(&bot, &message, &POOL).await?;
+        }
+        UserCommands::JoinQuestions => {
+            join_request::set_join_questions(&bot, &message, &POOL).await?;
+        }
+        UserCommands::JoinLog => {
+            join_request::set_join_log(&bot, &message, &POOL).await?;
+        }
+        UserCommands::CleanService => {
+            clean_service::set_clean_service(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Rules => {
+            rules::rules(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SetRules => {
+            rules::set_rules(&bot, &message, &POOL).await?;
+        }
+        UserCommands::ClearRules => {
+            rules::clear_rules(&bot, &message, &POOL).await?;
+        }
+        UserCommands::PrivateRules => {
+            rules::private_rules(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Lock => {
+            locks::lock(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Unlock => {
+            locks::unlock(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Locks => {
+            locks::locks(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Approve => {
+            approvals::approve(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Unapprove => {
+            approvals::unapprove(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Approved => {
+            approvals::approved(&bot, &message, &POOL).await?;
+        }
+        UserCommands::UnapproveAll => {
+            approvals::unapprove_all(&bot, &message).await?;
+        }
+        UserCommands::Disable => {
+            disabling::disable(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Enable => {
+            disabling::enable(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Disabled => {
+            disabling::disabled(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Disableable => {
+            disabling::disableable(&bot, &message).await?;
+        }
+        UserCommands::DisableDel => {
+            disabling::disable_del(&bot, &message, &POOL).await?;
+        }
+        UserCommands::SetLog => {
+            log_channel::set_log(&bot, &message, &POOL).await?;
+        }
+        UserCommands::UnsetLog => {
+            log_channel::unset_log(&bot, &message, &POOL).await?;
+        }
+        UserCommands::Log => {
+            log_channel::log(&bot, &message, &POOL).await?;
+        }
+        UserCommands::NoLog => {
+            log_channel::no_log(&bot, &message, &POOL).await?;
+        }
+        UserCommands::NewFed => {
+            feds::new_fed(&bot, &message, &POOL).await?;
+        }
+        UserCommands::JoinFed => {
+            feds::join_fed(&bot, &message, &POOL).await?;
+        }
+        UserCommands::LeaveFed => {
+            feds::leave_fed(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FPromote => {
+            feds::fed_promote(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FBan => {
+            feds::fed_ban(&bot, &message, &POOL).await?;
+        }
+        UserCommands::UnFBan => {
+            feds::fed_unban(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FedInfo => {
+            feds::fed_info(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FedChats => {
+            feds::fed_chats(&bot, &message, &POOL).await?;
+        }
+        UserCommands::FBanList => {
+            feds::fed_ban_list(&bot, &message, &POOL).await?;
+        }
+        UserCommands::AntiSpam => {
+            gbans::antispam(&bot, &message, &POOL).await?;
+        }
     };
 
+    let has_args = message
+        .text()
+        .is_some_and(|t| t.split_whitespace().count() > 1);
+    if !message.chat.is_private() && cmd.changes_settings(has_args) {
+        log_channel::log_settings_change(&bot, &message, &POOL).await;
+    }
+
     Ok(())
 }
 
-async fn sudo_cmd_handler(bot: TBot, msg: Message, cmd: SudoCommands) -> anyhow::Result<()> {
+async fn sudo_cmd_handler(
+    bot: TBot,
+    msg: Message,
+    cmd: SudoCommands,
+    cfg: ConfigParameters,
+) -> anyhow::Result<()> {
     save_details(&bot, &msg).await?;
     match cmd {
         SudoCommands::SHelp => {
             bot.send_message(msg.chat.id, "shelp command message")
                 .await?;
         }
+        SudoCommands::GBan => {
+            gbans::gban(&bot, &msg, &cfg.sudo, &POOL).await?;
+        }
+        SudoCommands::UnGBan => {
+            gbans::ungban(&bot, &msg, &POOL).await?;
+        }
+        SudoCommands::GBanList => {
+            gbans::gban_list(&bot, &msg, &POOL).await?;
+        }
+    };
+
+    Ok(())
+}
+
+async fn callback_handler(bot: TBot, query: CallbackQuery) -> anyhow::Result<()> {
+    let data = query.data.clone().unwrap_or_default();
+
+    // callback data is namespaced as `<action>:<args>`
+    match data.split_once(':') {
+        Some(("unpinall", args)) => pin::unpin_all_callback(&bot, &query, args).await?,
+        Some(("anonadmin", args)) => anon_admin::verify_callback(&bot, &query, args).await?,
+        Some(("rmwarn", args)) => warn::remove_warn_callback(&bot, &query, args, &POOL).await?,
+        Some(("report", args)) => report::report_callback(&bot, &query, args, &POOL).await?,
+        Some(("captcha", args)) => captcha::captcha_callback(&bot, &query, args).await?,
+        Some(("unapproveall", args)) => {
+            approvals::unapprove_all_callback(&bot, &query, args, &POOL).await?
+        }
+        Some(("joinreq", args)) => join_request::join_request_callback(&bot, &query, args).await?,
+        _ => {
+            log::warn!("Unhandled callback query: {data}");
+            bot.answer_callback_query(&query.id).await?;
+        }
     };
 
     Ok(())
@@ -1,5 +1,10 @@
 use teloxide::utils::command::BotCommands;
 
+use crate::utils::perms::{
+    Guard::{self, *},
+    Right::*,
+};
+
 #[derive(BotCommands, Clone)]
 #[command(
     rename_rule = "lowercase",
@@ -18,6 +23,345 @@ pub enum UserCommands {
     Delete,
     #[command(description = "get all notes in chat.")]
     Notes,
+    #[command(description = "mute a user.")]
+    Mute,
+    #[command(description = "temporarily mute a user, eg. /tmute @user 2h.")]
+    TMute,
+    #[command(description = "unmute a user.")]
+    Unmute,
+    #[command(description = "remove a user from the chat.")]
+    Kick,
+    #[command(description = "leave the chat.")]
+    KickMe,
+    #[command(description = "promote a user, with an optional title.")]
+    Promote,
+    #[command(description = "promote a user with all the rights the bot holds.")]
+    FullPromote,
+    #[command(description = "demote an admin promoted by the bot.")]
+    Demote,
+    #[command(description = "set the title of an admin.")]
+    Title,
+    #[command(description = "pin the replied message, optionally 'loud' or 'silent'.")]
+    Pin,
+    #[command(description = "unpin the replied or latest pinned message.")]
+    Unpin,
+    #[command(description = "unpin all messages in the chat.")]
+    UnpinAll,
+    #[command(description = "delete messages from the replied one, or the last n messages.")]
+    Purge,
+    #[command(description = "purge messages without a summary.")]
+    SPurge,
+    #[command(description = "delete the replied message.")]
+    Del,
+    #[command(description = "refresh the list of admins.")]
+    AdminCache,
+    #[command(description = "warn a user, with an optional reason.")]
+    Warn,
+    #[command(description = "delete the replied message and warn its sender.")]
+    DWarn,
+    #[command(description = "warn a user without announcing it.")]
+    SWarn,
+    #[command(description = "list the warnings of a user.")]
+    Warns,
+    #[command(description = "remove the latest warning of a user.")]
+    RmWarn,
+    #[command(description = "remove all warnings of a user.")]
+    ResetWarns,
+    #[command(description = "set the number of warnings before action is taken.")]
+    WarnLimit,
+    #[command(
+        description = "set the action taken at the warn limit: ban, kick, mute, tban or tmute."
+    )]
+    WarnMode,
+    #[command(description = "set how long warnings count toward the limit, eg. 7d or off.")]
+    WarnTime,
+    #[command(description = "report the replied message to the admins.")]
+    Report,
+    #[command(description = "toggle reports in a chat, or receiving them by PM.")]
+    Reports,
+    #[command(description = "show the antiflood settings of the chat.")]
+    Flood,
+    #[command(
+        description = "set the number of consecutive messages that trigger antiflood, or off."
+    )]
+    SetFlood,
+    #[command(
+        description = "set a number of messages in a number of seconds that trigger antiflood, or off."
+    )]
+    SetFloodTimer,
+    #[command(description = "set the action taken on flooders: ban, kick, mute, tban or tmute.")]
+    FloodMode,
+    #[command(description = "toggle deleting the messages of a flood.")]
+    ClearFlood,
+    #[command(description = "show the welcome of the chat, or turn welcomes on or off.")]
+    Welcome,
+    #[command(description = "set the welcome of the chat, or reply to a message to use it.")]
+    SetWelcome,
+    #[command(description = "restore the default welcome.")]
+    ResetWelcome,
+    #[command(description = "toggle deleting the previous welcome when someone new joins.")]
+    CleanWelcome,
+    #[command(description = "show the goodbye of the chat, or turn goodbyes on or off.")]
+    Goodbye,
+    #[command(description = "set the goodbye of the chat, or reply to a message to use it.")]
+    SetGoodbye,
+    #[command(description = "restore the default goodbye.")]
+    ResetGoodbye,
+    #[command(description = "toggle goodbyes for users kicked or banned by the bot.")]
+    GoodbyeKicked,
+    #[command(description = "show whether newcomers get a captcha, or turn captchas on or off.")]
+    Captcha,
+    #[command(description = "set the kind of captcha newcomers get: button, math or choice.")]
+    CaptchaMode,
+    #[command(description = "set how long newcomers have to solve their captcha.")]
+    CaptchaTime,
+    #[command(
+        description = "show or set how join requests are handled: manual, approve, decline or verify."
+    )]
+    JoinRequests,
+    #[command(
+        description = "set the questions asked to users verifying a join request, one per line."
+    )]
+    JoinQuestions,
+    #[command(description = "set the chat that receives the answers to join questions.")]
+    JoinLog,
+    #[command(
+        description = "pick the service messages to delete: join, leave, pin, title, photo, videochat, or on/off for all."
+    )]
+    CleanService,
+    #[command(description = "show the rules of the chat.")]
+    Rules,
+    #[command(description = "set the rules of the chat, or reply to a message to use it.")]
+    SetRules,
+    #[command(description = "clear the rules of the chat.")]
+    ClearRules,
+    #[command(description = "toggle sending the rules by PM instead of in the chat.")]
+    PrivateRules,
+    #[command(description = "lock kinds of messages for non-admins, eg. /lock sticker url.")]
+    Lock,
+    #[command(description = "unlock kinds of messages, eg. /unlock sticker.")]
+    Unlock,
+    #[command(description = "list what can be locked, and what is locked in the chat.")]
+    Locks,
+    #[command(description = "approve a user, so locks and antiflood don't apply to them.")]
+    Approve,
+    #[command(description = "remove the approval of a user.")]
+    Unapprove,
+    #[command(description = "list the approved users of the chat.")]
+    Approved,
+    #[command(description = "remove the approval of every user in the chat.")]
+    UnapproveAll,
+    #[command(description = "disable commands for non-admins, eg. /disable notes hashtag.")]
+    Disable,
+    #[command(description = "enable disabled commands, eg. /enable notes.")]
+    Enable,
+    #[command(description = "list the disabled commands of the chat.")]
+    Disabled,
+    #[command(description = "list the commands that can be disabled.")]
+    Disableable,
+    #[command(description = "toggle deleting the messages of disabled commands.")]
+    DisableDel,
+    #[command(description = "set the log channel, by forwarding a /setlog sent in the channel.")]
+    SetLog,
+    #[command(description = "stop logging actions to the log channel.")]
+    UnsetLog,
+    #[command(description = "show the log settings, or log categories, eg. /log warns notes.")]
+    Log,
+    #[command(description = "stop logging categories, eg. /nolog settings.")]
+    NoLog,
+    #[command(description = "create a federation, to share bans between chats.")]
+    NewFed,
+    #[command(description = "add the chat to a federation, eg. /joinfed <fed id>.")]
+    JoinFed,
+    #[command(description = "remove the chat from its federation.")]
+    LeaveFed,
+    #[command(description = "make a user an admin of the federation of the chat.")]
+    FPromote,
+    #[command(description = "ban a user in every chat of the federation.")]
+    FBan,
+    #[command(description = "lift the federation ban of a user.")]
+    UnFBan,
+    #[command(description = "show information about a federation.")]
+    FedInfo,
+    #[command(description = "list the chats of a federation.")]
+    FedChats,
+    #[command(description = "list the users banned in a federation.")]
+    FBanList,
+    #[command(description = "toggle removing globally banned users from the chat.")]
+    AntiSpam,
+}
+
+/// The commands admins can disable for non-admins, `hashtag` standing for #note fetching.
+pub const DISABLEABLE: &[&str] = &[
+    "flood", "get", "hashtag", "kickme", "locks", "notes", "report", "rules", "warns",
+];
+
+impl UserCommands {
+    /// The checks a command has to pass before it is run.
+    pub fn guards(&self) -> &'static [Guard] {
+        match self {
+            UserCommands::Help | UserCommands::Start => &[],
+            UserCommands::Get | UserCommands::Notes => &[],
+            UserCommands::Save | UserCommands::Delete => &[UserAdmin],
+            UserCommands::Mute
+            | UserCommands::TMute
+            | UserCommands::Unmute
+            | UserCommands::Kick => &[GroupChat, UserRight(CanRestrict), BotRight(CanRestrict)],
+            UserCommands::KickMe => &[GroupChat, BotRight(CanRestrict)],
+            UserCommands::Promote
+            | UserCommands::FullPromote
+            | UserCommands::Demote
+            | UserCommands::Title => &[GroupChat, UserRight(CanPromote), BotRight(CanPromote)],
+            UserCommands::Pin | UserCommands::Unpin | UserCommands::UnpinAll => {
+                &[GroupChat, UserRight(CanPin), BotRight(CanPin)]
+            }
+            UserCommands::Purge | UserCommands::SPurge | UserCommands::Del => {
+                &[GroupChat, UserRight(CanDelete), BotRight(CanDelete)]
+            }
+            // checks the live admin status itself, as the cached one may be outdated
+            UserCommands::AdminCache => &[GroupChat],
+            UserCommands::Warn | UserCommands::RmWarn | UserCommands::ResetWarns => {
+                &[GroupChat, UserRight(CanRestrict), BotRight(CanRestrict)]
+            }
+            UserCommands::DWarn | UserCommands::SWarn => &[
+                GroupChat,
+                UserRight(CanRestrict),
+                BotRight(CanRestrict),
+                BotRight(CanDelete),
+            ],
+            UserCommands::Warns => &[GroupChat],
+            UserCommands::Report => &[GroupChat],
+            // admins toggle reports in groups, anyone can opt in to PMs
+            UserCommands::Reports => &[UserAdmin],
+            UserCommands::WarnLimit | UserCommands::WarnMode | UserCommands::WarnTime => {
+                &[GroupChat, UserAdmin]
+            }
+            UserCommands::Flood => &[GroupChat],
+            UserCommands::SetFlood
+            | UserCommands::SetFloodTimer
+            | UserCommands::FloodMode
+            | UserCommands::ClearFlood => &[GroupChat, UserAdmin],
+            UserCommands::Welcome
+            | UserCommands::SetWelcome
+            | UserCommands::ResetWelcome
+            | UserCommands::CleanWelcome
+            | UserCommands::Goodbye
+            | UserCommands::SetGoodbye
+            | UserCommands::ResetGoodbye
+            | UserCommands::GoodbyeKicked => &[GroupChat, UserAdmin],
+            UserCommands::Captcha | UserCommands::CaptchaMode | UserCommands::CaptchaTime => {
+                &[GroupChat, UserAdmin]
+            }
+            UserCommands::JoinRequests | UserCommands::JoinQuestions | UserCommands::JoinLog => {
+                &[GroupChat, UserAdmin]
+            }
+            UserCommands::CleanService => &[GroupChat, UserAdmin],
+            UserCommands::Rules => &[GroupChat],
+            UserCommands::SetRules | UserCommands::ClearRules | UserCommands::PrivateRules => {
+                &[GroupChat, UserAdmin]
+            }
+            UserCommands::Lock | UserCommands::Unlock => {
+                &[GroupChat, UserRight(CanDelete), BotRight(CanDelete)]
+            }
+            UserCommands::Locks => &[GroupChat],
+            UserCommands::Approve | UserCommands::Unapprove | UserCommands::Approved => {
+                &[GroupChat, UserAdmin]
+            }
+            UserCommands::UnapproveAll => &[GroupChat, Owner],
+            UserCommands::Disable
+            | UserCommands::Enable
+            | UserCommands::Disabled
+            | UserCommands::DisableDel => &[GroupChat, UserAdmin],
+            UserCommands::Disableable => &[],
+            UserCommands::SetLog
+            | UserCommands::UnsetLog
+            | UserCommands::Log
+            | UserCommands::NoLog => &[GroupChat, UserAdmin],
+            // federation rights are checked against the federation, not the chat
+            UserCommands::NewFed
+            | UserCommands::FedInfo
+            | UserCommands::FedChats
+            | UserCommands::FBanList => &[],
+            UserCommands::JoinFed | UserCommands::LeaveFed => &[GroupChat, Owner],
+            UserCommands::FPromote => &[GroupChat],
+            UserCommands::FBan | UserCommands::UnFBan => &[GroupChat, BotRight(CanRestrict)],
+            UserCommands::AntiSpam => &[GroupChat, UserAdmin],
+        }
+    }
+
+    /// Whether a command acts as the user who sent it, so anonymous admins have to say who they
+    /// are first.
+    pub fn identifies_sender(&self) -> bool {
+        self.guards().iter().any(|g| g.checks_user())
+            || matches!(
+                self,
+                UserCommands::KickMe
+                    | UserCommands::Warns
+                    | UserCommands::Report
+                    | UserCommands::NewFed
+                    | UserCommands::FPromote
+                    | UserCommands::FBan
+                    | UserCommands::UnFBan
+            )
+    }
+
+    /// Whether a command changes the settings of the chat, and so is sent to the log channel.
+    ///
+    /// Most settings commands only show the current settings when given no arguments.
+    pub fn changes_settings(&self, has_args: bool) -> bool {
+        match self {
+            UserCommands::SetWelcome
+            | UserCommands::ResetWelcome
+            | UserCommands::SetGoodbye
+            | UserCommands::ResetGoodbye
+            | UserCommands::SetRules
+            | UserCommands::ClearRules => true,
+            UserCommands::Reports
+            | UserCommands::WarnLimit
+            | UserCommands::WarnMode
+            | UserCommands::WarnTime
+            | UserCommands::SetFlood
+            | UserCommands::SetFloodTimer
+            | UserCommands::FloodMode
+            | UserCommands::ClearFlood
+            | UserCommands::Welcome
+            | UserCommands::CleanWelcome
+            | UserCommands::Goodbye
+            | UserCommands::GoodbyeKicked
+            | UserCommands::Captcha
+            | UserCommands::CaptchaMode
+            | UserCommands::CaptchaTime
+            | UserCommands::JoinRequests
+            | UserCommands::JoinQuestions
+            | UserCommands::JoinLog
+            | UserCommands::CleanService
+            | UserCommands::PrivateRules
+            | UserCommands::Lock
+            | UserCommands::Unlock
+            | UserCommands::Disable
+            | UserCommands::Enable
+            | UserCommands::DisableDel
+            | UserCommands::Log
+            | UserCommands::NoLog
+            | UserCommands::AntiSpam => has_args,
+            _ => false,
+        }
+    }
+
+    /// The name a command is disabled by, if admins can disable it.
+    pub fn disable_name(&self) -> Option<&'static str> {
+        match self {
+            UserCommands::Flood => Some("flood"),
+            UserCommands::Get => Some("get"),
+            UserCommands::KickMe => Some("kickme"),
+            UserCommands::Locks => Some("locks"),
+            UserCommands::Notes => Some("notes"),
+            UserCommands::Report => Some("report"),
+            UserCommands::Rules => Some("rules"),
+            UserCommands::Warns => Some("warns"),
+            _ => None,
+        }
+    }
 }
 
 #[derive(BotCommands, Clone)]
@@ -25,4 +369,30 @@ pub enum UserCommands {
 pub enum SudoCommands {
     #[command(description = "sudo help.")]
     SHelp,
+    #[command(description = "ban a user from every chat, eg. /gban <user> <reason>.")]
+    GBan,
+    #[command(description = "lift the global ban of a user.")]
+    UnGBan,
+    #[command(description = "list the globally banned users.")]
+    GBanList,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disableable_matches_disable_names() {
+        let mut names: Vec<_> = UserCommands::bot_commands()
+            .iter()
+            .filter_map(|c| UserCommands::parse(&c.command, "bot").ok())
+            .filter_map(|cmd| cmd.disable_name())
+            .collect();
+        names.push("hashtag");
+        names.sort();
+
+        let mut disableable = DISABLEABLE.to_vec();
+        disableable.sort();
+        assert_eq!(names, disableable);
+    }
 }
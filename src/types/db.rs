@@ -1,4 +1,4 @@
-
+use chrono::{DateTime, Utc};
 
 pub struct User {
     pub user_id: i64,
@@ -17,3 +17,120 @@ pub struct Note {
     pub note_id: String,
     pub note_content: String,
 }
+
+#[allow(dead_code)]
+pub struct Warn {
+    pub warn_id: i32,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub reason: Option<String>,
+    pub issuer_id: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[allow(dead_code)]
+pub struct WarnSettings {
+    pub chat_id: i64,
+    pub warn_limit: i32,
+    pub warn_mode: String,
+    pub warn_mode_duration: Option<i64>,
+    pub warn_time: Option<i64>,
+}
+
+#[allow(dead_code)]
+pub struct FloodSettings {
+    pub chat_id: i64,
+    pub flood_limit: i32,
+    pub flood_timer_count: i32,
+    pub flood_timer_secs: i32,
+    pub flood_mode: String,
+    pub flood_mode_duration: Option<i64>,
+    pub clear_flood: bool,
+}
+
+#[allow(dead_code)]
+pub struct Greetings {
+    pub chat_id: i64,
+    pub welcome_enabled: bool,
+    pub welcome_text: Option<String>,
+    pub welcome_media_kind: Option<String>,
+    pub welcome_file_id: Option<String>,
+    pub clean_welcome: bool,
+    pub last_welcome_id: Option<i32>,
+    pub goodbye_enabled: bool,
+    pub goodbye_text: Option<String>,
+    pub goodbye_media_kind: Option<String>,
+    pub goodbye_file_id: Option<String>,
+    pub goodbye_kicked: bool,
+}
+
+#[allow(dead_code)]
+pub struct CaptchaSettings {
+    pub chat_id: i64,
+    pub captcha_enabled: bool,
+    pub captcha_mode: String,
+    pub captcha_time: i64,
+}
+
+#[allow(dead_code)]
+pub struct PendingCaptcha {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub message_id: i32,
+    pub answer: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[allow(dead_code)]
+pub struct JoinSettings {
+    pub chat_id: i64,
+    pub join_mode: String,
+    pub join_questions: Vec<String>,
+    pub join_log_chat: Option<i64>,
+}
+
+#[allow(dead_code)]
+pub struct Rules {
+    pub chat_id: i64,
+    pub rules_text: Option<String>,
+    pub private_rules: bool,
+}
+
+#[allow(dead_code)]
+pub struct DisabledCommands {
+    pub chat_id: i64,
+    pub commands: Vec<String>,
+    pub delete_disabled: bool,
+}
+
+#[allow(dead_code)]
+pub struct LogSettings {
+    pub chat_id: i64,
+    pub log_chat_id: Option<i64>,
+    pub log_categories: Vec<String>,
+}
+
+#[allow(dead_code)]
+pub struct Fed {
+    pub fed_id: String,
+    pub fed_name: String,
+    pub owner_id: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[allow(dead_code)]
+pub struct FedBan {
+    pub fed_id: String,
+    pub user_id: i64,
+    pub reason: Option<String>,
+    pub banned_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[allow(dead_code)]
+pub struct GBan {
+    pub user_id: i64,
+    pub reason: Option<String>,
+    pub banned_by: i64,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,3 +1,6 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
 use teloxide::{
     adaptors::{DefaultParseMode, Throttle},
     prelude::*,
@@ -13,3 +16,329 @@ pub type TBot = DefaultParseMode<Throttle<Bot>>;
 pub struct ConfigParameters {
     pub sudo: Vec<UserId>,
 }
+
+/// An action taken against a user, eg. once they hit the warn limit of a chat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Punishment {
+    Ban,
+    Kick,
+    Mute,
+    TBan,
+    TMute,
+}
+
+impl Punishment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Punishment::Ban => "ban",
+            Punishment::Kick => "kick",
+            Punishment::Mute => "mute",
+            Punishment::TBan => "tban",
+            Punishment::TMute => "tmute",
+        }
+    }
+
+    /// Whether the punishment only lasts for a limited time.
+    pub fn is_timed(&self) -> bool {
+        matches!(self, Punishment::TBan | Punishment::TMute)
+    }
+}
+
+impl FromStr for Punishment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ban" => Ok(Punishment::Ban),
+            "kick" => Ok(Punishment::Kick),
+            "mute" => Ok(Punishment::Mute),
+            "tban" => Ok(Punishment::TBan),
+            "tmute" => Ok(Punishment::TMute),
+            _ => Err(anyhow!(
+                "Unknown action, use one of ban, kick, mute, tban or tmute."
+            )),
+        }
+    }
+}
+
+/// The kind of media attached to a stored message, eg. a welcome.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MediaKind {
+    Photo,
+    Video,
+    Animation,
+    Document,
+    Sticker,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Photo => "photo",
+            MediaKind::Video => "video",
+            MediaKind::Animation => "animation",
+            MediaKind::Document => "document",
+            MediaKind::Sticker => "sticker",
+        }
+    }
+}
+
+impl FromStr for MediaKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "photo" => Ok(MediaKind::Photo),
+            "video" => Ok(MediaKind::Video),
+            "animation" => Ok(MediaKind::Animation),
+            "document" => Ok(MediaKind::Document),
+            "sticker" => Ok(MediaKind::Sticker),
+            _ => Err(anyhow!("Unknown media kind: {s}")),
+        }
+    }
+}
+
+/// The kind of challenge newcomers have to solve before they can talk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptchaMode {
+    Button,
+    Math,
+    Choice,
+}
+
+impl CaptchaMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptchaMode::Button => "button",
+            CaptchaMode::Math => "math",
+            CaptchaMode::Choice => "choice",
+        }
+    }
+}
+
+impl FromStr for CaptchaMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "button" => Ok(CaptchaMode::Button),
+            "math" => Ok(CaptchaMode::Math),
+            "choice" => Ok(CaptchaMode::Choice),
+            _ => Err(anyhow!(
+                "Unknown captcha mode, use one of button, math or choice."
+            )),
+        }
+    }
+}
+
+/// How join requests to a chat are handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoinMode {
+    /// Requests are left for the admins.
+    Manual,
+    Approve,
+    Decline,
+    /// Users are approved once they pass a challenge or answer the join questions by PM.
+    Verify,
+}
+
+impl JoinMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JoinMode::Manual => "manual",
+            JoinMode::Approve => "approve",
+            JoinMode::Decline => "decline",
+            JoinMode::Verify => "verify",
+        }
+    }
+}
+
+impl FromStr for JoinMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "manual" => Ok(JoinMode::Manual),
+            "approve" => Ok(JoinMode::Approve),
+            "decline" => Ok(JoinMode::Decline),
+            "verify" => Ok(JoinMode::Verify),
+            _ => Err(anyhow!(
+                "Unknown join mode, use one of manual, approve, decline or verify."
+            )),
+        }
+    }
+}
+
+/// The kinds of service messages that can be cleaned up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ServiceKind {
+    Join,
+    Leave,
+    Pin,
+    Title,
+    Photo,
+    VideoChat,
+}
+
+impl ServiceKind {
+    pub const ALL: [ServiceKind; 6] = [
+        ServiceKind::Join,
+        ServiceKind::Leave,
+        ServiceKind::Pin,
+        ServiceKind::Title,
+        ServiceKind::Photo,
+        ServiceKind::VideoChat,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceKind::Join => "join",
+            ServiceKind::Leave => "leave",
+            ServiceKind::Pin => "pin",
+            ServiceKind::Title => "title",
+            ServiceKind::Photo => "photo",
+            ServiceKind::VideoChat => "videochat",
+        }
+    }
+}
+
+impl FromStr for ServiceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ServiceKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s.to_lowercase())
+            .ok_or(anyhow!(
+                "Unknown service message, use one of join, leave, pin, title, photo or videochat."
+            ))
+    }
+}
+
+/// The kinds of messages that can be locked in a chat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LockKind {
+    Sticker,
+    Gif,
+    Photo,
+    Video,
+    Document,
+    Voice,
+    Audio,
+    Url,
+    Forward,
+    Inline,
+    Game,
+    Poll,
+    Contact,
+    Location,
+    Command,
+    Emoji,
+    Rtl,
+    InviteLink,
+    Bots,
+}
+
+impl LockKind {
+    pub const ALL: [LockKind; 19] = [
+        LockKind::Sticker,
+        LockKind::Gif,
+        LockKind::Photo,
+        LockKind::Video,
+        LockKind::Document,
+        LockKind::Voice,
+        LockKind::Audio,
+        LockKind::Url,
+        LockKind::Forward,
+        LockKind::Inline,
+        LockKind::Game,
+        LockKind::Poll,
+        LockKind::Contact,
+        LockKind::Location,
+        LockKind::Command,
+        LockKind::Emoji,
+        LockKind::Rtl,
+        LockKind::InviteLink,
+        LockKind::Bots,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LockKind::Sticker => "sticker",
+            LockKind::Gif => "gif",
+            LockKind::Photo => "photo",
+            LockKind::Video => "video",
+            LockKind::Document => "document",
+            LockKind::Voice => "voice",
+            LockKind::Audio => "audio",
+            LockKind::Url => "url",
+            LockKind::Forward => "forward",
+            LockKind::Inline => "inline",
+            LockKind::Game => "game",
+            LockKind::Poll => "poll",
+            LockKind::Contact => "contact",
+            LockKind::Location => "location",
+            LockKind::Command => "command",
+            LockKind::Emoji => "emoji",
+            LockKind::Rtl => "rtl",
+            LockKind::InviteLink => "invitelink",
+            LockKind::Bots => "bots",
+        }
+    }
+}
+
+impl FromStr for LockKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LockKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s.to_lowercase())
+            .ok_or(anyhow!(
+                "Unknown lock {s}, use /locks to see what can be locked."
+            ))
+    }
+}
+
+/// The kinds of actions that can be sent to the log channel of a chat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogCategory {
+    Settings,
+    Admin,
+    Warns,
+    Notes,
+    Automated,
+}
+
+impl LogCategory {
+    pub const ALL: [LogCategory; 5] = [
+        LogCategory::Settings,
+        LogCategory::Admin,
+        LogCategory::Warns,
+        LogCategory::Notes,
+        LogCategory::Automated,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogCategory::Settings => "settings",
+            LogCategory::Admin => "admin",
+            LogCategory::Warns => "warns",
+            LogCategory::Notes => "notes",
+            LogCategory::Automated => "automated",
+        }
+    }
+}
+
+impl FromStr for LogCategory {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LogCategory::ALL
+            .into_iter()
+            .find(|category| category.as_str() == s.to_lowercase())
+            .ok_or(anyhow!(
+                "Unknown log category {s}, use one of settings, admin, warns, notes or automated."
+            ))
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use teloxide::{
+    prelude::*,
+    types::{ChatMember, ChatMemberUpdated},
+};
+
+/// How long a chat's admin list is trusted before it is fetched again.
+const ADMIN_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+lazy_static! {
+    static ref ADMIN_CACHE: Mutex<HashMap<ChatId, (Instant, Vec<ChatMember>)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Returns the admins of a chat, fetching them from telegram if the cached list is stale.
+pub async fn get_chat_admins(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+) -> anyhow::Result<Vec<ChatMember>> {
+    if let Some((fetched_at, admins)) = ADMIN_CACHE.lock().unwrap().get(&chat_id) {
+        if fetched_at.elapsed() < ADMIN_CACHE_TTL {
+            return Ok(admins.clone());
+        }
+    }
+
+    refresh(bot, chat_id).await
+}
+
+/// Returns the chat member for the given user if they are an admin of the chat.
+pub async fn get_admin(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+) -> anyhow::Result<Option<ChatMember>> {
+    Ok(get_chat_admins(bot, chat_id)
+        .await?
+        .into_iter()
+        .find(|admin| admin.user.id == user_id))
+}
+
+/// Fetches the admins of a chat from telegram and caches them.
+pub async fn refresh(bot: &crate::types::TBot, chat_id: ChatId) -> anyhow::Result<Vec<ChatMember>> {
+    let admins = bot.get_chat_administrators(chat_id).await?;
+
+    ADMIN_CACHE
+        .lock()
+        .unwrap()
+        .insert(chat_id, (Instant::now(), admins.clone()));
+
+    Ok(admins)
+}
+
+pub fn invalidate(chat_id: ChatId) {
+    ADMIN_CACHE.lock().unwrap().remove(&chat_id);
+}
+
+/// Drops the cached admins of a chat when a member is promoted or demoted.
+pub async fn chat_member_handler(update: ChatMemberUpdated) -> anyhow::Result<()> {
+    let was_admin = update.old_chat_member.is_privileged();
+    let is_admin = update.new_chat_member.is_privileged();
+
+    if was_admin || is_admin {
+        invalidate(update.chat.id);
+    }
+
+    Ok(())
+}
@@ -1,11 +1,15 @@
+use chrono::{DateTime, Utc};
 use sqlx::{Pool, Postgres};
 use teloxide::types::{ChatKind, Message};
 use tokio;
 
 use crate::{
     types::{
-        db::{Chat, Note, User},
-        TBot,
+        db::{
+            CaptchaSettings, Chat, DisabledCommands, Fed, FedBan, FloodSettings, GBan, Greetings,
+            JoinSettings, LogSettings, Note, PendingCaptcha, Rules, User, Warn, WarnSettings,
+        },
+        LogCategory, TBot,
     },
     POOL,
 };
@@ -13,8 +17,8 @@ use crate::{
 pub async fn save_details(bot: &TBot, message: &Message) -> anyhow::Result<()> {
     // opportunistically save user/chat details to db
     tokio::try_join!(
-        save_user_handler(bot, message, &*POOL),
-        save_chat_handler(bot, message, &*POOL)
+        save_user_handler(bot, message, &POOL),
+        save_chat_handler(bot, message, &POOL)
     )?;
 
     Ok(())
@@ -154,6 +158,15 @@ pub async fn get_chat(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Cha
     Ok(chat)
 }
 
+/// Returns a chat, or `None` if the bot doesn't know it.
+pub async fn find_chat(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Option<Chat>> {
+    Ok(
+        sqlx::query_as!(Chat, "SELECT * FROM chats WHERE chat_id = $1", chat_id)
+            .fetch_optional(pool)
+            .await?,
+    )
+}
+
 pub async fn delete_note(chat_id: i64, note_id: &str, pool: &Pool<Postgres>) -> anyhow::Result<()> {
     sqlx::query!(
         "DELETE FROM notes WHERE chat_id = $1 AND note_id = $2",
@@ -172,3 +185,1421 @@ pub async fn get_all_notes(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Resul
             .await?,
     )
 }
+
+pub async fn insert_warn(
+    chat_id: i64,
+    user_id: i64,
+    reason: Option<&str>,
+    issuer_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Warn> {
+    let warn = sqlx::query_as!(
+        Warn,
+        r#"
+        INSERT into warns (chat_id, user_id, reason, issuer_id) VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#,
+        chat_id,
+        user_id,
+        reason,
+        issuer_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(warn)
+}
+
+/// Fetches the warnings of a user, ignoring the ones issued before `since` if given.
+pub async fn get_warns(
+    chat_id: i64,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<Warn>> {
+    Ok(sqlx::query_as!(
+        Warn,
+        r#"
+        SELECT * FROM warns WHERE chat_id = $1 AND user_id = $2
+        AND ($3::TIMESTAMPTZ IS NULL OR created_at > $3)
+        ORDER BY created_at
+        "#,
+        chat_id,
+        user_id,
+        since,
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Deletes a warning, unless it was issued before `since` and so already expired.
+pub async fn delete_warn(
+    chat_id: i64,
+    warn_id: i32,
+    since: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<Warn>> {
+    Ok(sqlx::query_as!(
+        Warn,
+        r#"
+        DELETE FROM warns WHERE chat_id = $1 AND warn_id = $2
+        AND ($3::TIMESTAMPTZ IS NULL OR created_at > $3)
+        RETURNING *
+        "#,
+        chat_id,
+        warn_id,
+        since,
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Deletes the latest warning of a user, ignoring the ones issued before `since` if given.
+pub async fn delete_latest_warn(
+    chat_id: i64,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<Warn>> {
+    Ok(sqlx::query_as!(
+        Warn,
+        r#"
+        DELETE FROM warns WHERE warn_id = (
+            SELECT warn_id FROM warns WHERE chat_id = $1 AND user_id = $2
+            AND ($3::TIMESTAMPTZ IS NULL OR created_at > $3)
+            ORDER BY created_at DESC LIMIT 1
+        )
+        RETURNING *
+        "#,
+        chat_id,
+        user_id,
+        since,
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn reset_warns(chat_id: i64, user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<u64> {
+    let res = sqlx::query!(
+        "DELETE FROM warns WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+pub async fn get_warn_settings(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<WarnSettings> {
+    let settings = sqlx::query_as!(
+        WarnSettings,
+        "SELECT * FROM warn_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // chats that never changed their settings use the defaults
+    Ok(settings.unwrap_or(WarnSettings {
+        chat_id,
+        warn_limit: 3,
+        warn_mode: "ban".to_owned(),
+        warn_mode_duration: None,
+        warn_time: None,
+    }))
+}
+
+pub async fn set_warn_limit(
+    chat_id: i64,
+    warn_limit: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into warn_settings (chat_id, warn_limit) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET warn_limit = excluded.warn_limit
+        "#,
+        chat_id,
+        warn_limit
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_warn_mode(
+    chat_id: i64,
+    warn_mode: &str,
+    warn_mode_duration: Option<i64>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into warn_settings (chat_id, warn_mode, warn_mode_duration) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET (warn_mode, warn_mode_duration) = (excluded.warn_mode, excluded.warn_mode_duration)
+        "#,
+        chat_id,
+        warn_mode,
+        warn_mode_duration
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_warn_time(
+    chat_id: i64,
+    warn_time: Option<i64>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into warn_settings (chat_id, warn_time) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET warn_time = excluded.warn_time
+        "#,
+        chat_id,
+        warn_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_report_setting(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<bool>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT enabled FROM report_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn set_report_setting(
+    chat_id: i64,
+    enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into report_settings (chat_id, enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET enabled = excluded.enabled
+        "#,
+        chat_id,
+        enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns which of the given users opted in to receiving reports by PM.
+pub async fn get_report_pm_users(
+    user_ids: &[i64],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<i64>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT chat_id FROM report_settings WHERE chat_id = ANY($1) AND enabled",
+        user_ids
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_flood_settings(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<FloodSettings> {
+    let settings = sqlx::query_as!(
+        FloodSettings,
+        "SELECT * FROM flood_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // antiflood is off unless a chat sets it up
+    Ok(settings.unwrap_or(FloodSettings {
+        chat_id,
+        flood_limit: 0,
+        flood_timer_count: 0,
+        flood_timer_secs: 0,
+        flood_mode: "mute".to_owned(),
+        flood_mode_duration: None,
+        clear_flood: false,
+    }))
+}
+
+pub async fn set_flood_limit(
+    chat_id: i64,
+    flood_limit: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into flood_settings (chat_id, flood_limit) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET flood_limit = excluded.flood_limit
+        "#,
+        chat_id,
+        flood_limit
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_flood_timer(
+    chat_id: i64,
+    flood_timer_count: i32,
+    flood_timer_secs: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into flood_settings (chat_id, flood_timer_count, flood_timer_secs) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET (flood_timer_count, flood_timer_secs) = (excluded.flood_timer_count, excluded.flood_timer_secs)
+        "#,
+        chat_id,
+        flood_timer_count,
+        flood_timer_secs
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_flood_mode(
+    chat_id: i64,
+    flood_mode: &str,
+    flood_mode_duration: Option<i64>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into flood_settings (chat_id, flood_mode, flood_mode_duration) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET (flood_mode, flood_mode_duration) = (excluded.flood_mode, excluded.flood_mode_duration)
+        "#,
+        chat_id,
+        flood_mode,
+        flood_mode_duration
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_clear_flood(
+    chat_id: i64,
+    clear_flood: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into flood_settings (chat_id, clear_flood) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET clear_flood = excluded.clear_flood
+        "#,
+        chat_id,
+        clear_flood
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_greetings(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Greetings> {
+    let greetings = sqlx::query_as!(
+        Greetings,
+        "SELECT * FROM greetings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // chats get the default welcome until they set their own
+    Ok(greetings.unwrap_or(Greetings {
+        chat_id,
+        welcome_enabled: true,
+        welcome_text: None,
+        welcome_media_kind: None,
+        welcome_file_id: None,
+        clean_welcome: false,
+        last_welcome_id: None,
+        goodbye_enabled: false,
+        goodbye_text: None,
+        goodbye_media_kind: None,
+        goodbye_file_id: None,
+        goodbye_kicked: true,
+    }))
+}
+
+/// Sets the welcome of a chat, `None` everywhere restores the default one.
+pub async fn set_welcome(
+    chat_id: i64,
+    welcome_text: Option<&str>,
+    welcome_media_kind: Option<&str>,
+    welcome_file_id: Option<&str>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, welcome_text, welcome_media_kind, welcome_file_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET (welcome_text, welcome_media_kind, welcome_file_id) = (excluded.welcome_text, excluded.welcome_media_kind, excluded.welcome_file_id)
+        "#,
+        chat_id,
+        welcome_text,
+        welcome_media_kind,
+        welcome_file_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_welcome_enabled(
+    chat_id: i64,
+    welcome_enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, welcome_enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET welcome_enabled = excluded.welcome_enabled
+        "#,
+        chat_id,
+        welcome_enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_clean_welcome(
+    chat_id: i64,
+    clean_welcome: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, clean_welcome) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET clean_welcome = excluded.clean_welcome
+        "#,
+        chat_id,
+        clean_welcome
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_last_welcome_id(
+    chat_id: i64,
+    last_welcome_id: Option<i32>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, last_welcome_id) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET last_welcome_id = excluded.last_welcome_id
+        "#,
+        chat_id,
+        last_welcome_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Sets the goodbye of a chat, `None` everywhere restores the default one.
+pub async fn set_goodbye(
+    chat_id: i64,
+    goodbye_text: Option<&str>,
+    goodbye_media_kind: Option<&str>,
+    goodbye_file_id: Option<&str>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, goodbye_text, goodbye_media_kind, goodbye_file_id) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET (goodbye_text, goodbye_media_kind, goodbye_file_id) = (excluded.goodbye_text, excluded.goodbye_media_kind, excluded.goodbye_file_id)
+        "#,
+        chat_id,
+        goodbye_text,
+        goodbye_media_kind,
+        goodbye_file_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_goodbye_enabled(
+    chat_id: i64,
+    goodbye_enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, goodbye_enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET goodbye_enabled = excluded.goodbye_enabled
+        "#,
+        chat_id,
+        goodbye_enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_goodbye_kicked(
+    chat_id: i64,
+    goodbye_kicked: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into greetings (chat_id, goodbye_kicked) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET goodbye_kicked = excluded.goodbye_kicked
+        "#,
+        chat_id,
+        goodbye_kicked
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_captcha_settings(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<CaptchaSettings> {
+    let settings = sqlx::query_as!(
+        CaptchaSettings,
+        "SELECT * FROM captcha_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(settings.unwrap_or(CaptchaSettings {
+        chat_id,
+        captcha_enabled: false,
+        captcha_mode: "button".to_owned(),
+        captcha_time: 300,
+    }))
+}
+
+pub async fn set_captcha_enabled(
+    chat_id: i64,
+    captcha_enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into captcha_settings (chat_id, captcha_enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET captcha_enabled = excluded.captcha_enabled
+        "#,
+        chat_id,
+        captcha_enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_captcha_mode(
+    chat_id: i64,
+    captcha_mode: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into captcha_settings (chat_id, captcha_mode) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET captcha_mode = excluded.captcha_mode
+        "#,
+        chat_id,
+        captcha_mode
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_captcha_time(
+    chat_id: i64,
+    captcha_time: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into captcha_settings (chat_id, captcha_time) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET captcha_time = excluded.captcha_time
+        "#,
+        chat_id,
+        captcha_time
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn add_pending_captcha(
+    captcha: &PendingCaptcha,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into pending_captchas (chat_id, user_id, message_id, answer, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (chat_id, user_id) DO
+        UPDATE SET message_id = excluded.message_id, answer = excluded.answer,
+            expires_at = excluded.expires_at
+        "#,
+        captcha.chat_id,
+        captcha.user_id,
+        captcha.message_id,
+        captcha.answer,
+        captcha.expires_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Removes the captcha a user has to solve, returning it if there was one.
+pub async fn take_pending_captcha(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<PendingCaptcha>> {
+    Ok(sqlx::query_as!(
+        PendingCaptcha,
+        "DELETE FROM pending_captchas WHERE chat_id = $1 AND user_id = $2 RETURNING *",
+        chat_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Removes a captcha that expired, unless it was solved or replaced by a newer one.
+pub async fn remove_pending_captcha(
+    chat_id: i64,
+    user_id: i64,
+    message_id: i32,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM pending_captchas WHERE chat_id = $1 AND user_id = $2 AND message_id = $3",
+        chat_id,
+        user_id,
+        message_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_pending_captchas(pool: &Pool<Postgres>) -> anyhow::Result<Vec<PendingCaptcha>> {
+    Ok(
+        sqlx::query_as!(PendingCaptcha, "SELECT * FROM pending_captchas")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn get_join_settings(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<JoinSettings> {
+    let settings = sqlx::query_as!(
+        JoinSettings,
+        "SELECT * FROM join_settings WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // join requests are left for the admins unless a chat says otherwise
+    Ok(settings.unwrap_or(JoinSettings {
+        chat_id,
+        join_mode: "manual".to_owned(),
+        join_questions: Vec::new(),
+        join_log_chat: None,
+    }))
+}
+
+pub async fn set_join_mode(
+    chat_id: i64,
+    join_mode: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into join_settings (chat_id, join_mode) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET join_mode = excluded.join_mode
+        "#,
+        chat_id,
+        join_mode
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_join_questions(
+    chat_id: i64,
+    join_questions: &[String],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into join_settings (chat_id, join_questions) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET join_questions = excluded.join_questions
+        "#,
+        chat_id,
+        join_questions
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_join_log_chat(
+    chat_id: i64,
+    join_log_chat: Option<i64>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into join_settings (chat_id, join_log_chat) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET join_log_chat = excluded.join_log_chat
+        "#,
+        chat_id,
+        join_log_chat
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns the kinds of service messages that are deleted in a chat.
+pub async fn get_clean_service(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT service_kinds FROM clean_service WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or_default())
+}
+
+pub async fn set_clean_service(
+    chat_id: i64,
+    service_kinds: &[String],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into clean_service (chat_id, service_kinds) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET service_kinds = excluded.service_kinds
+        "#,
+        chat_id,
+        service_kinds
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_rules(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Rules> {
+    let rules = sqlx::query_as!(Rules, "SELECT * FROM rules WHERE chat_id = $1", chat_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(rules.unwrap_or(Rules {
+        chat_id,
+        rules_text: None,
+        private_rules: false,
+    }))
+}
+
+/// Sets the rules of a chat, `None` clears them.
+pub async fn set_rules(
+    chat_id: i64,
+    rules_text: Option<&str>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into rules (chat_id, rules_text) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET rules_text = excluded.rules_text
+        "#,
+        chat_id,
+        rules_text
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_private_rules(
+    chat_id: i64,
+    private_rules: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into rules (chat_id, private_rules) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET private_rules = excluded.private_rules
+        "#,
+        chat_id,
+        private_rules
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns the kinds of messages that are locked in a chat.
+pub async fn get_locks(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Vec<String>> {
+    Ok(
+        sqlx::query_scalar!("SELECT lock_kinds FROM locks WHERE chat_id = $1", chat_id)
+            .fetch_optional(pool)
+            .await?
+            .unwrap_or_default(),
+    )
+}
+
+pub async fn set_locks(
+    chat_id: i64,
+    lock_kinds: &[String],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into locks (chat_id, lock_kinds) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET lock_kinds = excluded.lock_kinds
+        "#,
+        chat_id,
+        lock_kinds
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Approves a user in a chat, returning `false` if they were approved already.
+pub async fn approve_user(
+    chat_id: i64,
+    user_id: i64,
+    approved_by: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        r#"
+        INSERT into approvals (chat_id, user_id, approved_by) VALUES ($1, $2, $3)
+        ON CONFLICT (chat_id, user_id) DO NOTHING
+        "#,
+        chat_id,
+        user_id,
+        approved_by
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Removes the approval of a user, returning `false` if they weren't approved.
+pub async fn unapprove_user(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM approvals WHERE chat_id = $1 AND user_id = $2",
+        chat_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn unapprove_all(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<u64> {
+    let res = sqlx::query!("DELETE FROM approvals WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+pub async fn is_approved(
+    chat_id: i64,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM approvals WHERE chat_id = $1 AND user_id = $2)",
+        chat_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?
+    .unwrap_or_default())
+}
+
+/// Returns the approved users of a chat along with their names, if known.
+pub async fn get_approved_users(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, Option<String>)>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT approvals.user_id, users.full_name AS "full_name?" FROM approvals
+        LEFT JOIN users ON users.user_id = approvals.user_id
+        WHERE approvals.chat_id = $1
+        ORDER BY approvals.created_at
+        "#,
+        chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.user_id, r.full_name)).collect())
+}
+
+pub async fn get_disabled_commands(
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<DisabledCommands> {
+    let disabled = sqlx::query_as!(
+        DisabledCommands,
+        "SELECT * FROM disabled_commands WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(disabled.unwrap_or(DisabledCommands {
+        chat_id,
+        commands: Vec::new(),
+        delete_disabled: false,
+    }))
+}
+
+pub async fn set_disabled_commands(
+    chat_id: i64,
+    commands: &[String],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into disabled_commands (chat_id, commands) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET commands = excluded.commands
+        "#,
+        chat_id,
+        commands
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_delete_disabled(
+    chat_id: i64,
+    delete_disabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into disabled_commands (chat_id, delete_disabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET delete_disabled = excluded.delete_disabled
+        "#,
+        chat_id,
+        delete_disabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_log_settings(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<LogSettings> {
+    let settings = sqlx::query_as!(
+        LogSettings,
+        "SELECT * FROM log_channels WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(settings.unwrap_or(LogSettings {
+        chat_id,
+        log_chat_id: None,
+        log_categories: LogCategory::ALL
+            .iter()
+            .map(|c| c.as_str().to_owned())
+            .collect(),
+    }))
+}
+
+/// Sets the channel a chat logs its actions to, `None` stops logging.
+pub async fn set_log_chat(
+    chat_id: i64,
+    log_chat_id: Option<i64>,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into log_channels (chat_id, log_chat_id) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET log_chat_id = excluded.log_chat_id
+        "#,
+        chat_id,
+        log_chat_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_log_categories(
+    chat_id: i64,
+    log_categories: &[String],
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into log_channels (chat_id, log_categories) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET log_categories = excluded.log_categories
+        "#,
+        chat_id,
+        log_categories
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Creates a federation, each user can own a single one.
+pub async fn create_fed(
+    fed_name: &str,
+    owner_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Fed> {
+    Ok(sqlx::query_as!(
+        Fed,
+        "INSERT into feds (fed_name, owner_id) VALUES ($1, $2) RETURNING *",
+        fed_name,
+        owner_id
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+pub async fn get_fed(fed_id: &str, pool: &Pool<Postgres>) -> anyhow::Result<Option<Fed>> {
+    Ok(
+        sqlx::query_as!(Fed, "SELECT * FROM feds WHERE fed_id = $1", fed_id)
+            .fetch_optional(pool)
+            .await?,
+    )
+}
+
+pub async fn get_fed_by_owner(owner_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Option<Fed>> {
+    Ok(
+        sqlx::query_as!(Fed, "SELECT * FROM feds WHERE owner_id = $1", owner_id)
+            .fetch_optional(pool)
+            .await?,
+    )
+}
+
+/// Returns the federation a chat is part of, if any.
+pub async fn get_chat_fed(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Option<Fed>> {
+    Ok(sqlx::query_as!(
+        Fed,
+        r#"
+        SELECT feds.* FROM feds
+        JOIN fed_chats ON fed_chats.fed_id = feds.fed_id
+        WHERE fed_chats.chat_id = $1
+        "#,
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Adds a chat to a federation, moving it out of the one it was part of.
+pub async fn join_fed(chat_id: i64, fed_id: &str, pool: &Pool<Postgres>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into fed_chats (chat_id, fed_id) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET fed_id = excluded.fed_id
+        "#,
+        chat_id,
+        fed_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn leave_fed(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let res = sqlx::query!("DELETE FROM fed_chats WHERE chat_id = $1", chat_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Returns the chats of a federation along with their names, if known.
+pub async fn get_fed_chats(
+    fed_id: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(i64, Option<String>)>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT fed_chats.chat_id, chats.chat_name FROM fed_chats
+        JOIN chats ON chats.chat_id = fed_chats.chat_id
+        WHERE fed_chats.fed_id = $1
+        ORDER BY fed_chats.chat_id
+        "#,
+        fed_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.chat_id, r.chat_name)).collect())
+}
+
+/// Makes a user an admin of a federation, returning `false` if they were one already.
+pub async fn add_fed_admin(
+    fed_id: &str,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        r#"
+        INSERT into fed_admins (fed_id, user_id) VALUES ($1, $2)
+        ON CONFLICT (fed_id, user_id) DO NOTHING
+        "#,
+        fed_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Whether a user owns a federation or is one of its admins.
+pub async fn is_fed_admin(fed: &Fed, user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    if fed.owner_id == user_id {
+        return Ok(true);
+    }
+
+    Ok(sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM fed_admins WHERE fed_id = $1 AND user_id = $2)",
+        fed.fed_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?
+    .unwrap_or_default())
+}
+
+/// Bans a user in a federation, updating the reason if they were banned already.
+pub async fn fed_ban(
+    fed_id: &str,
+    user_id: i64,
+    reason: Option<&str>,
+    banned_by: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into fed_bans (fed_id, user_id, reason, banned_by) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (fed_id, user_id) DO
+        UPDATE SET reason = excluded.reason, banned_by = excluded.banned_by
+        "#,
+        fed_id,
+        user_id,
+        reason,
+        banned_by
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Lifts the ban of a user in a federation, returning `false` if they weren't banned.
+pub async fn fed_unban(fed_id: &str, user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM fed_bans WHERE fed_id = $1 AND user_id = $2",
+        fed_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Records that a federation ban was applied in a chat.
+pub async fn add_fed_ban_chat(
+    fed_id: &str,
+    user_id: i64,
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into fed_ban_chats (fed_id, user_id, chat_id) VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        fed_id,
+        user_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns the chats the federation ban of a user was applied in.
+pub async fn get_fed_ban_chats(
+    fed_id: &str,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<i64>> {
+    Ok(sqlx::query_scalar!(
+        "SELECT chat_id FROM fed_ban_chats WHERE fed_id = $1 AND user_id = $2",
+        fed_id,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_fed_ban(
+    fed_id: &str,
+    user_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Option<FedBan>> {
+    Ok(sqlx::query_as!(
+        FedBan,
+        "SELECT * FROM fed_bans WHERE fed_id = $1 AND user_id = $2",
+        fed_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Returns the bans of a federation along with the names of the banned users, if known.
+pub async fn get_fed_bans(
+    fed_id: &str,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<Vec<(FedBan, Option<String>)>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT fed_bans.*, users.full_name AS "full_name?" FROM fed_bans
+        LEFT JOIN users ON users.user_id = fed_bans.user_id
+        WHERE fed_bans.fed_id = $1
+        ORDER BY fed_bans.created_at
+        "#,
+        fed_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let ban = FedBan {
+                fed_id: r.fed_id,
+                user_id: r.user_id,
+                reason: r.reason,
+                banned_by: r.banned_by,
+                created_at: r.created_at,
+            };
+            (ban, r.full_name)
+        })
+        .collect())
+}
+
+/// Counts the admins, chats and bans of a federation.
+pub async fn get_fed_stats(fed_id: &str, pool: &Pool<Postgres>) -> anyhow::Result<(i64, i64, i64)> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM fed_admins WHERE fed_id = $1) AS "admins!",
+            (SELECT COUNT(*) FROM fed_chats WHERE fed_id = $1) AS "chats!",
+            (SELECT COUNT(*) FROM fed_bans WHERE fed_id = $1) AS "bans!"
+        "#,
+        fed_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok((row.admins, row.chats, row.bans))
+}
+
+/// Bans a user globally, updating the reason if they were banned already.
+pub async fn gban(
+    user_id: i64,
+    reason: Option<&str>,
+    banned_by: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into gbans (user_id, reason, banned_by) VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO
+        UPDATE SET reason = excluded.reason, banned_by = excluded.banned_by
+        "#,
+        user_id,
+        reason,
+        banned_by
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Lifts the global ban of a user, returning `false` if they weren't banned.
+pub async fn ungban(user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    let res = sqlx::query!("DELETE FROM gbans WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Records that a global ban was applied in a chat.
+pub async fn add_gban_chat(
+    user_id: i64,
+    chat_id: i64,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        "INSERT into gban_chats (user_id, chat_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        user_id,
+        chat_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns the chats the global ban of a user was applied in.
+pub async fn get_gban_chats(user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Vec<i64>> {
+    Ok(
+        sqlx::query_scalar!("SELECT chat_id FROM gban_chats WHERE user_id = $1", user_id)
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn get_gban(user_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<Option<GBan>> {
+    Ok(
+        sqlx::query_as!(GBan, "SELECT * FROM gbans WHERE user_id = $1", user_id)
+            .fetch_optional(pool)
+            .await?,
+    )
+}
+
+/// Returns the global bans along with the names of the banned users, if known.
+pub async fn get_gbans(pool: &Pool<Postgres>) -> anyhow::Result<Vec<(GBan, Option<String>)>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT gbans.*, users.full_name AS "full_name?" FROM gbans
+        LEFT JOIN users ON users.user_id = gbans.user_id
+        ORDER BY gbans.created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let gban = GBan {
+                user_id: r.user_id,
+                reason: r.reason,
+                banned_by: r.banned_by,
+                created_at: r.created_at,
+            };
+            (gban, r.full_name)
+        })
+        .collect())
+}
+
+/// Returns the group chats global bans apply to, ie. those that didn't opt out.
+pub async fn get_antispam_chats(pool: &Pool<Postgres>) -> anyhow::Result<Vec<i64>> {
+    Ok(sqlx::query_scalar!(
+        r#"
+        SELECT chat_id FROM chats
+        WHERE chat_id < 0 AND chat_id NOT IN (
+            SELECT chat_id FROM antispam WHERE NOT antispam_enabled
+        )
+        "#
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_antispam(chat_id: i64, pool: &Pool<Postgres>) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar!(
+        "SELECT antispam_enabled FROM antispam WHERE chat_id = $1",
+        chat_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or(true))
+}
+
+pub async fn set_antispam(
+    chat_id: i64,
+    antispam_enabled: bool,
+    pool: &Pool<Postgres>,
+) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT into antispam (chat_id, antispam_enabled) VALUES ($1, $2)
+        ON CONFLICT (chat_id) DO
+        UPDATE SET antispam_enabled = excluded.antispam_enabled
+        "#,
+        chat_id,
+        antispam_enabled
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
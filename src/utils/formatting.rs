@@ -0,0 +1,215 @@
+use teloxide::{
+    prelude::*,
+    types::{Chat, InlineKeyboardButton, InlineKeyboardMarkup, InputFile, MessageId, User},
+    utils::html,
+    RequestError,
+};
+
+use crate::types::MediaKind;
+
+/// Marks a button in stored texts, eg. `[Rules](buttonurl://t.me/somechannel)`.
+const BUTTON_MARKER: &str = "](buttonurl://";
+/// Appended to a button url to put the button on the same row as the previous one.
+const SAME_ROW_SUFFIX: &str = ":same";
+
+/// Replaces fillings such as `{first}` or `{mention}` with details of the user and chat.
+pub fn fill(text: &str, user: &User, chat: &Chat) -> String {
+    let mention = html::user_mention(user.id.0 as i64, &user.full_name());
+    let username = user
+        .username
+        .as_ref()
+        .map(|u| format!("@{u}"))
+        .unwrap_or_else(|| mention.clone());
+
+    text.replace("{first}", &html::escape(&user.first_name))
+        .replace(
+            "{last}",
+            &html::escape(user.last_name.as_deref().unwrap_or_default()),
+        )
+        .replace("{fullname}", &html::escape(&user.full_name()))
+        .replace("{username}", &username)
+        .replace("{mention}", &mention)
+        .replace("{id}", &user.id.to_string())
+        .replace(
+            "{chatname}",
+            &html::escape(chat.title().unwrap_or("the chat")),
+        )
+}
+
+/// Undoes the escaping Telegram applies to HTML texts, for button labels and urls.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+/// Splits the buttons off a stored text, returning the remaining text and their keyboard.
+///
+/// Buttons are written as `[label](buttonurl://url)` and each get their own row, unless the url
+/// ends with `:same`. Buttons with invalid urls are left in the text as they are.
+pub fn parse_buttons(text: &str) -> (String, Option<InlineKeyboardMarkup>) {
+    let mut remaining = String::new();
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+    let mut rest = text;
+
+    while let Some(marker) = rest.find(BUTTON_MARKER) {
+        let after = &rest[marker + BUTTON_MARKER.len()..];
+        let (Some(open), Some(close)) = (rest[..marker].rfind('['), after.find(')')) else {
+            remaining.push_str(&rest[..marker + BUTTON_MARKER.len()]);
+            rest = after;
+            continue;
+        };
+
+        let label = unescape(&rest[open + 1..marker]);
+        let target = &after[..close];
+        let (target, same_row) = match target.strip_suffix(SAME_ROW_SUFFIX) {
+            Some(target) => (target, true),
+            None => (target, false),
+        };
+
+        let url = unescape(target);
+        let url = if url.contains("://") {
+            url
+        } else {
+            format!("https://{url}")
+        };
+
+        match url.parse() {
+            Ok(url) if !label.trim().is_empty() => {
+                remaining.push_str(&rest[..open]);
+                let button = InlineKeyboardButton::url(label.trim(), url);
+                match rows.last_mut() {
+                    Some(row) if same_row => row.push(button),
+                    _ => rows.push(vec![button]),
+                }
+            }
+            _ => remaining.push_str(&rest[..marker + BUTTON_MARKER.len() + close + 1]),
+        }
+
+        rest = &after[close + 1..];
+    }
+    remaining.push_str(rest);
+
+    let keyboard = (!rows.is_empty()).then(|| InlineKeyboardMarkup::new(rows));
+    (remaining.trim().to_owned(), keyboard)
+}
+
+/// Returns the media of a message that can be stored and sent again later.
+pub fn extract_media(message: &Message) -> Option<(MediaKind, String)> {
+    // animations are sent as documents as well, so they are checked first
+    if let Some(photo) = message.photo().and_then(|sizes| sizes.last()) {
+        Some((MediaKind::Photo, photo.file.id.clone()))
+    } else if let Some(video) = message.video() {
+        Some((MediaKind::Video, video.file.id.clone()))
+    } else if let Some(animation) = message.animation() {
+        Some((MediaKind::Animation, animation.file.id.clone()))
+    } else if let Some(document) = message.document() {
+        Some((MediaKind::Document, document.file.id.clone()))
+    } else {
+        message
+            .sticker()
+            .map(|sticker| (MediaKind::Sticker, sticker.file.id.clone()))
+    }
+}
+
+/// Sends a stored text, with its buttons and optionally some media.
+///
+/// Stickers can't have a caption, so the text is dropped for them.
+pub async fn send_content(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    text: &str,
+    media: Option<(MediaKind, &str)>,
+    reply_to: Option<MessageId>,
+) -> Result<Message, RequestError> {
+    let (text, keyboard) = parse_buttons(text);
+
+    macro_rules! send {
+        ($request:expr) => {{
+            let mut request = $request;
+            if let Some(keyboard) = keyboard {
+                request = request.reply_markup(keyboard);
+            }
+            if let Some(reply_to) = reply_to {
+                request = request.reply_to_message_id(reply_to);
+            }
+            request.await
+        }};
+    }
+
+    match media {
+        None => send!(bot.send_message(chat_id, text)),
+        Some((kind, file_id)) => {
+            let file = InputFile::file_id(file_id);
+            match kind {
+                MediaKind::Photo => send!(bot.send_photo(chat_id, file).caption(text)),
+                MediaKind::Video => send!(bot.send_video(chat_id, file).caption(text)),
+                MediaKind::Animation => send!(bot.send_animation(chat_id, file).caption(text)),
+                MediaKind::Document => send!(bot.send_document(chat_id, file).caption(text)),
+                MediaKind::Sticker => {
+                    // unlike the other requests, stickers take a raw message id to reply to
+                    let mut request = bot.send_sticker(chat_id, file);
+                    if let Some(keyboard) = keyboard {
+                        request = request.reply_markup(keyboard);
+                    }
+                    if let Some(reply_to) = reply_to {
+                        request = request.reply_to_message_id(reply_to.0);
+                    }
+                    request.await
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(keyboard: &InlineKeyboardMarkup) -> Vec<Vec<&str>> {
+        keyboard
+            .inline_keyboard
+            .iter()
+            .map(|row| row.iter().map(|b| b.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        let (text, keyboard) = parse_buttons("Welcome to the chat!");
+        assert_eq!(text, "Welcome to the chat!");
+        assert!(keyboard.is_none());
+    }
+
+    #[test]
+    fn splits_buttons_into_rows() {
+        let (text, keyboard) = parse_buttons(
+            "Hi!\n[Rules](buttonurl://t.me/a)\n[Site](buttonurl://https://example.com)[Chat](buttonurl://t.me/b:same)",
+        );
+        assert_eq!(text, "Hi!");
+        assert_eq!(
+            labels(&keyboard.unwrap()),
+            vec![vec!["Rules"], vec!["Site", "Chat"]]
+        );
+    }
+
+    #[test]
+    fn unescapes_urls() {
+        let (_, keyboard) = parse_buttons("[Go](buttonurl://example.com/?a=1&amp;b=2)");
+        let button = &keyboard.unwrap().inline_keyboard[0][0];
+        match &button.kind {
+            teloxide::types::InlineKeyboardButtonKind::Url(url) => {
+                assert_eq!(url.as_str(), "https://example.com/?a=1&b=2")
+            }
+            kind => panic!("unexpected button kind: {kind:?}"),
+        }
+    }
+
+    #[test]
+    fn keeps_malformed_buttons_in_text() {
+        let (text, keyboard) = parse_buttons("[](buttonurl://t.me/a) and Rules](buttonurl://x");
+        assert_eq!(text, "[](buttonurl://t.me/a) and Rules](buttonurl://x");
+        assert!(keyboard.is_none());
+    }
+}
@@ -1,13 +1,15 @@
-
 use sqlx::{Pool, Postgres};
 use teloxide::{
     payloads::SendMessageSetters,
     requests::Requester,
-    types::{ChatId, Message, MessageEntity, MessageEntityKind},
+    types::{ChatId, Message, MessageEntityKind},
 };
 
+pub mod admin_cache;
 pub mod db;
+pub mod formatting;
 pub mod perms;
+pub mod time;
 
 pub async fn extract_user_and_text(
     bot: &crate::types::TBot,
@@ -30,48 +32,34 @@ pub async fn extract_user_and_text(
 
         let mut user_id: Option<u64> = None; // user id to return
         let mut text: Option<String> = Some(split_text[1].to_owned()); // text to return
-        let mut ent: Option<&MessageEntity> = None; // mentioned entity in message
 
         // if entities exist in message
-        if let Some(entities) = message.entities() {
-            // filter out only text mention entities
-            let filtered_entities = entities
+        if let Some(entities) = message.parse_entities() {
+            // use first text mention entity for extracting user
+            let mention = entities
                 .iter()
-                .filter(|ent| matches!(ent.kind, MessageEntityKind::TextMention { user: _ }));
-
-            // use first entity for extracting user
-            if !filtered_entities.count() == 0 {
-                ent = Some(&entities[0]);
-            }
+                .find(|ent| matches!(ent.kind(), MessageEntityKind::TextMention { user: _ }));
 
             // if entity offset matches (command end/text start) then all is well
-            if !entities.is_empty() && ent.is_some() {
-                if ent.unwrap().offset
-                    == msg_text.len() - text_to_parse.len()
-                {
-                    ent = Some(&entities[0]);
-                    user_id = match &ent.unwrap().kind {
-                        MessageEntityKind::TextMention { user } => Some(user.id.0),
-                        _ => None,
-                    };
-
-                    text = Some(
-                        msg_text[ent.unwrap().offset + ent.unwrap().length..].to_owned(),
-                    );
+            if let Some(ent) =
+                mention.filter(|ent| ent.start() == msg_text.len() - text_to_parse.len())
+            {
+                if let MessageEntityKind::TextMention { user } = ent.kind() {
+                    user_id = Some(user.id.0);
                 }
-            } 
+
+                text = Some(msg_text[ent.end()..].trim_start().to_owned());
+            }
             // args exist and first arg is a @ mention
             else if !args.is_empty() && args[0].starts_with('@') {
                 let user_name = args[0];
                 let res =
                     db::get_user(None, Some(user_name.to_string().replace('@', "")), pool).await;
 
-                if res.is_ok() {
-                    user_id = Some(res.unwrap().user_id as u64);
+                if let Ok(user) = res {
+                    user_id = Some(user.user_id as u64);
                     let split: Vec<_> = msg_text.splitn(3, char::is_whitespace).collect();
-                    if split.len() >= 3 {
-                        text = Some(split[2].to_owned());
-                    }
+                    text = split.get(2).map(|s| s.to_string());
                 } else {
                     bot.send_message(
                         message.chat.id,
@@ -83,44 +71,39 @@ pub async fn extract_user_and_text(
                     return (None, None);
                 }
             }
-            // check if first argument is a user ID
-            else if !args.is_empty() {
-                if let Ok(id) = args[0].parse::<u64>() {
-                    user_id = Some(id);
-                    let res: Vec<_> = msg_text.splitn(3, char::is_whitespace).collect();
-                    if res.len() >= 3 {
-                        text = Some(res[2].to_owned());
-                    }
+            // check if first argument is a user ID; in a reply it's only text (e.g. a duration)
+            else if let Some(id) = args
+                .first()
+                .filter(|_| message.reply_to_message().is_none())
+                .and_then(|arg| arg.parse::<u64>().ok())
+            {
+                // check if bot has interacted with this user before
+                if bot.get_chat(ChatId(id as i64)).await.is_err() {
+                    // haven't seen this user, bail
+                    bot.send_message(
+                        message.chat.id,
+                        "I don't seem to have interacted with this user before - please forward a message from them to give me control!",
+                    ).reply_to_message_id(message.id).await.ok();
+                    return (None, None);
                 }
-            } 
-            // check if command is a reply to message
-            else if message.reply_to_message().is_some() {
-                (user_id, text) = id_from_reply(bot, message);
-            } else {
+
+                user_id = Some(id);
+                let res: Vec<_> = msg_text.splitn(3, char::is_whitespace).collect();
+                text = res.get(2).map(|s| s.to_string());
+            }
+            // check if command is a reply to message, the args are then only text
+            else if let Some(user) = message.reply_to_message().and_then(|m| m.from()) {
+                user_id = Some(user.id.0);
+            } else if args.is_empty() {
                 // nothing satisfied, bail
                 return (None, None);
             }
-
-            // check if bot has interacted with this user before
-            if let Some(id) = user_id {
-                match bot.get_chat(ChatId(id as i64)).await {
-                    Ok(_) => {}
-                    Err(_) => {
-                        // haven't seen this user, bail
-                        bot.send_message(
-                            message.chat.id, 
-                            "I don't seem to have interacted with this user before - please forward a message from them to give me control!",
-                        ).reply_to_message_id(message.id).await.ok();
-                        return (None, None);
-                    }
-                }
-            }
         }
 
-    // return user ID and extracted text
-    return (user_id, text);
+        // return user ID and extracted text
+        return (user_id, text);
     }
-    
+
     (None, None)
 }
 
@@ -153,4 +136,4 @@ pub fn id_from_reply(
 
     // nothing found, bail
     (None, None)
-}
\ No newline at end of file
+}
@@ -1,11 +1,11 @@
-
-
-use anyhow::{anyhow};
+use anyhow::anyhow;
 use teloxide::{
     prelude::*,
-    types::{ChatMember, ChatMemberStatus},
+    types::{ChatMember, ChatMemberKind},
 };
 
+use crate::utils::admin_cache;
+
 pub async fn require_user_admin(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
     let user_id = match message.from() {
         Some(user) => user.id,
@@ -34,11 +34,214 @@ pub async fn is_user_admin(
         return Ok(());
     }
 
-    let chat_member: ChatMember = bot.get_chat_member(message.chat.id, user_id).await?;
+    match admin_cache::get_admin(bot, message.chat.id, user_id).await? {
+        Some(_) => Ok(()),
+        None => Err(anyhow!("User is not admin")),
+    }
+}
+
+/// Admin rights that can be required from a user or the bot.
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug)]
+pub enum Right {
+    CanRestrict,
+    CanDelete,
+    CanPin,
+    CanPromote,
+}
+
+impl Right {
+    fn description(&self) -> &'static str {
+        match self {
+            Right::CanRestrict => "restrict members",
+            Right::CanDelete => "delete messages",
+            Right::CanPin => "pin messages",
+            Right::CanPromote => "add new admins",
+        }
+    }
+
+    fn held_by(&self, member: &ChatMember) -> bool {
+        match &member.kind {
+            ChatMemberKind::Owner(_) => true,
+            ChatMemberKind::Administrator(admin) => match self {
+                Right::CanRestrict => admin.can_restrict_members,
+                Right::CanDelete => admin.can_delete_messages,
+                Right::CanPin => admin.can_pin_messages,
+                Right::CanPromote => admin.can_promote_members,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Checks a command has to pass before it is run, see [`UserCommands::guards`].
+///
+/// [`UserCommands::guards`]: crate::types::commands::UserCommands::guards
+#[derive(Clone, Copy, Debug)]
+pub enum Guard {
+    GroupChat,
+    UserAdmin,
+    Owner,
+    UserRight(Right),
+    BotRight(Right),
+}
+
+impl Guard {
+    /// Whether the guard checks the user running the command, rather than the chat or the bot.
+    pub fn checks_user(&self) -> bool {
+        matches!(self, Guard::UserAdmin | Guard::Owner | Guard::UserRight(_))
+    }
+}
+
+/// Runs the given guards in order, stopping at the first one that fails.
+pub async fn require_guards(
+    bot: &crate::types::TBot,
+    message: &Message,
+    guards: &[Guard],
+) -> anyhow::Result<()> {
+    for guard in guards {
+        match *guard {
+            Guard::GroupChat => require_group_chat(bot, message).await?,
+            Guard::UserAdmin => require_user_admin(bot, message).await?,
+            Guard::Owner => require_owner(bot, message).await?,
+            Guard::UserRight(right) => require_user_right(bot, message, right).await?,
+            Guard::BotRight(right) => require_bot_right(bot, message, right).await?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Quietly checks whether a user passes the user related guards of a command.
+pub async fn user_passes_guards(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+    guards: &[Guard],
+) -> anyhow::Result<bool> {
+    for guard in guards {
+        let passes = match *guard {
+            Guard::UserAdmin => admin_cache::get_admin(bot, chat_id, user_id)
+                .await?
+                .is_some(),
+            Guard::Owner => admin_cache::get_admin(bot, chat_id, user_id)
+                .await?
+                .is_some_and(|m| m.is_owner()),
+            Guard::UserRight(right) => has_right(bot, chat_id, user_id, right).await?,
+            Guard::GroupChat | Guard::BotRight(_) => true,
+        };
+
+        if !passes {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Whether the message was sent by an admin posting anonymously on behalf of the chat.
+pub fn is_anonymous_admin(message: &Message) -> bool {
+    message
+        .sender_chat()
+        .is_some_and(|chat| chat.id == message.chat.id)
+}
+
+pub async fn has_right(
+    bot: &crate::types::TBot,
+    chat_id: ChatId,
+    user_id: UserId,
+    right: Right,
+) -> anyhow::Result<bool> {
+    let chat_member = admin_cache::get_admin(bot, chat_id, user_id).await?;
+
+    Ok(chat_member.is_some_and(|m| right.held_by(&m)))
+}
+
+pub async fn require_user_right(
+    bot: &crate::types::TBot,
+    message: &Message,
+    right: Right,
+) -> anyhow::Result<()> {
+    let user_id = match message.from() {
+        Some(user) => user.id,
+        None => {
+            return Err(anyhow!("User not found"));
+        }
+    };
+
+    if !has_right(bot, message.chat.id, user_id, right).await? {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "You need to be an admin with the right to {} for this to work!",
+                right.description()
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Err(anyhow!(
+            "User is missing the right to {}",
+            right.description()
+        ));
+    }
+
+    Ok(())
+}
+
+pub async fn require_bot_right(
+    bot: &crate::types::TBot,
+    message: &Message,
+    right: Right,
+) -> anyhow::Result<()> {
+    if !has_right(bot, message.chat.id, UserId(*crate::BOT_ID as u64), right).await? {
+        bot.send_message(
+            message.chat.id,
+            format!(
+                "I need to be an admin with the right to {} for this to work!",
+                right.description()
+            ),
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Err(anyhow!(
+            "Bot is missing the right to {}",
+            right.description()
+        ));
+    }
+
+    Ok(())
+}
 
-    match chat_member.status() {
-        ChatMemberStatus::Administrator => Ok(()),
-        ChatMemberStatus::Owner => Ok(()),
-        _ => Err(anyhow!("User is not admin")),
+pub async fn require_owner(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    let user_id = match message.from() {
+        Some(user) => user.id,
+        None => {
+            return Err(anyhow!("User not found"));
+        }
+    };
+
+    let chat_member = admin_cache::get_admin(bot, message.chat.id, user_id).await?;
+
+    if !chat_member.is_some_and(|m| m.is_owner()) {
+        bot.send_message(message.chat.id, "Only the owner of this chat can do this!")
+            .reply_to_message_id(message.id)
+            .await?;
+        return Err(anyhow!("User is not the chat owner"));
     }
+
+    Ok(())
+}
+
+pub async fn require_group_chat(bot: &crate::types::TBot, message: &Message) -> anyhow::Result<()> {
+    if message.chat.is_private() || message.chat.is_channel() {
+        bot.send_message(
+            message.chat.id,
+            "This command is meant to be used in groups!",
+        )
+        .reply_to_message_id(message.id)
+        .await?;
+        return Err(anyhow!("Command used outside of a group"));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,154 @@
+use anyhow::anyhow;
+use chrono::Duration;
+
+/// Telegram treats restrictions shorter than this as permanent.
+pub const MIN_DURATION_SECS: i64 = 30;
+/// Telegram treats restrictions longer than this as permanent.
+pub const MAX_DURATION_SECS: i64 = 366 * 24 * 60 * 60;
+
+/// Parses human-friendly durations such as `30s`, `10m`, `2h`, `1d12h` or `1w`.
+///
+/// Durations outside of the range Telegram accepts for timed restrictions are rejected.
+pub fn parse_duration(text: &str) -> anyhow::Result<Duration> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(anyhow!(
+            "You need to give me a duration, eg. 30m, 2h or 1d12h!"
+        ));
+    }
+
+    let mut total: i64 = 0;
+    let mut amount = String::new();
+
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            amount.push(c);
+            continue;
+        }
+
+        let unit_secs = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => {
+                return Err(anyhow!(
+                    "'{c}' is not a valid time unit; use s, m, h, d or w."
+                ))
+            }
+        };
+
+        if amount.is_empty() {
+            return Err(anyhow!(
+                "Every time unit needs a number in front of it, eg. 10m."
+            ));
+        }
+
+        total = amount
+            .parse::<i64>()
+            .ok()
+            .and_then(|n| n.checked_mul(unit_secs))
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or(anyhow!("That duration is way too long!"))?;
+        amount.clear();
+    }
+
+    if !amount.is_empty() {
+        return Err(anyhow!(
+            "'{amount}' is missing a time unit; use s, m, h, d or w."
+        ));
+    }
+
+    if total < MIN_DURATION_SECS {
+        return Err(anyhow!("Durations need to be at least 30 seconds long."));
+    }
+
+    if total > MAX_DURATION_SECS {
+        return Err(anyhow!("Durations can't be longer than 366 days."));
+    }
+
+    Ok(Duration::seconds(total))
+}
+
+/// Formats a duration in the same units accepted by [`parse_duration`], eg. `1d 12h`.
+pub fn format_duration(duration: Duration) -> String {
+    let mut secs = duration.num_seconds();
+    let mut parts = vec![];
+
+    for (unit, unit_secs) in [
+        ("w", 604800),
+        ("d", 86400),
+        ("h", 3600),
+        ("m", 60),
+        ("s", 1),
+    ] {
+        if secs >= unit_secs {
+            parts.push(format!("{}{unit}", secs / unit_secs));
+            secs %= unit_secs;
+        }
+    }
+
+    if parts.is_empty() {
+        return "0s".to_owned();
+    }
+
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_units() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::seconds(30));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::minutes(10));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::hours(2));
+        assert_eq!(parse_duration("3d").unwrap(), Duration::days(3));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::weeks(1));
+    }
+
+    #[test]
+    fn parses_compound_durations() {
+        assert_eq!(
+            parse_duration("1d12h").unwrap(),
+            Duration::days(1) + Duration::hours(12)
+        );
+        assert_eq!(
+            parse_duration("1h30m15s").unwrap(),
+            Duration::hours(1) + Duration::minutes(30) + Duration::seconds(15)
+        );
+        assert_eq!(parse_duration(" 2H ").unwrap(), Duration::hours(2));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1d12").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("1.5h").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_durations() {
+        assert!(parse_duration("29s").is_err());
+        assert!(parse_duration("367d").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+        assert!(parse_duration("366d").is_ok());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::seconds(45)), "45s");
+        assert_eq!(format_duration(Duration::hours(36)), "1d 12h");
+        assert_eq!(
+            format_duration(Duration::weeks(1) + Duration::minutes(5)),
+            "1w 5m"
+        );
+        assert_eq!(format_duration(Duration::zero()), "0s");
+    }
+}